@@ -0,0 +1,351 @@
+//! Times the Intcode processor on the longer puzzle programs, with and without its cache of
+//! decoded instructions, against the interpreter it replaced. Run with `cargo bench`.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+use advent_2019::intcode::{parse_program, InputFn, OutputFn, Processor, ProcessorState};
+
+const RUNS: usize = 10;
+
+/// The interpreter the puzzle solutions each carried a copy of before `Processor` was written,
+/// as it was in day 9 apart from its debugging output. Memory is a plain vector grown as it is
+/// addressed, and every instruction is decoded with `%` and `/` as it is executed.
+mod baseline {
+    use std::collections::VecDeque;
+
+    #[derive(Debug, Clone)]
+    pub struct Processor {
+        code: Vec<i64>,
+        pc: usize,
+        rel: i64,
+    }
+
+    impl Processor {
+        pub fn new(code: Vec<i64>) -> Processor {
+            Processor {
+                code,
+                pc: 0,
+                rel: 0,
+            }
+        }
+
+        pub fn execute(&mut self, mut input: VecDeque<i64>) -> (ProcessorState, VecDeque<i64>) {
+            let mut output: VecDeque<i64> = VecDeque::new();
+            let mut state: Option<ProcessorState> = None;
+
+            while {
+                let raw_opcode = self.code[self.pc];
+                let (opcode, (param1_mode, param2_mode, param3_mode)) =
+                    Processor::parse_opcode(raw_opcode);
+
+                if opcode == 99 {
+                    state = Some(ProcessorState::Halted);
+                    false
+                } else if opcode == 1 {
+                    // add in1 in2 out_addr
+                    let (arg1, arg2, out_addr) =
+                        self.parse_inst_data_data_addr(param1_mode, param2_mode, param3_mode);
+                    self.code[out_addr] = arg1 + arg2;
+                    self.pc += 4;
+                    true
+                } else if opcode == 2 {
+                    // mul in1 in2 out_addr
+                    let (arg1, arg2, out_addr) =
+                        self.parse_inst_data_data_addr(param1_mode, param2_mode, param3_mode);
+                    self.code[out_addr] = arg1 * arg2;
+                    self.pc += 4;
+                    true
+                } else if opcode == 3 {
+                    // input write_addr
+                    let write_addr = self.parse_inst_addr(param1_mode);
+                    match input.pop_front() {
+                        None => {
+                            state = Some(ProcessorState::IoWait);
+                            false
+                        }
+                        Some(input_val) => {
+                            self.code[write_addr] = input_val;
+                            self.pc += 2;
+                            true
+                        }
+                    }
+                } else if opcode == 4 {
+                    // output read_addr
+                    let value = self.parse_inst_data(param1_mode);
+                    output.push_back(value);
+                    self.pc += 2;
+                    true
+                } else if opcode == 5 {
+                    // jump-if-true cond addr
+                    let (cond, addr) = self.parse_inst_data_data(param1_mode, param2_mode);
+                    if cond != 0 {
+                        self.pc = addr as usize;
+                    } else {
+                        self.pc += 3;
+                    }
+                    true
+                } else if opcode == 6 {
+                    // jump-if-false cond addr
+                    let (cond, addr) = self.parse_inst_data_data(param1_mode, param2_mode);
+                    if cond == 0 {
+                        self.pc = addr as usize;
+                    } else {
+                        self.pc += 3;
+                    }
+                    true
+                } else if opcode == 7 {
+                    // less-than val1 val2 out_addr
+                    let (arg1, arg2, out_addr) =
+                        self.parse_inst_data_data_addr(param1_mode, param2_mode, param3_mode);
+                    self.code[out_addr] = if arg1 < arg2 { 1 } else { 0 };
+                    self.pc += 4;
+                    true
+                } else if opcode == 8 {
+                    // equals val1 val2 out_addr
+                    let (arg1, arg2, out_addr) =
+                        self.parse_inst_data_data_addr(param1_mode, param2_mode, param3_mode);
+                    self.code[out_addr] = if arg1 == arg2 { 1 } else { 0 };
+                    self.pc += 4;
+                    true
+                } else if opcode == 9 {
+                    // rel val
+                    let arg1 = self.parse_inst_data(param1_mode);
+                    self.rel += arg1;
+                    self.pc += 2;
+                    true
+                } else {
+                    panic!("Unexpected opcode {} at {}", raw_opcode, self.pc)
+                }
+            } {}
+
+            (
+                state.expect("Execution ended without setting a state!"),
+                output,
+            )
+        }
+
+        fn parse_opcode(raw_opcode: i64) -> (i64, (ParamMode, ParamMode, ParamMode)) {
+            (
+                raw_opcode % 100,
+                (
+                    Processor::parse_mode(raw_opcode, 100),
+                    Processor::parse_mode(raw_opcode, 1_000),
+                    Processor::parse_mode(raw_opcode, 10_000),
+                ),
+            )
+        }
+
+        fn parse_mode(raw_opcode: i64, offset: i64) -> ParamMode {
+            let raw_mode = raw_opcode % (offset * 10) / offset;
+            match raw_mode {
+                0 => ParamMode::Position,
+                1 => ParamMode::Immediate,
+                2 => ParamMode::Relative,
+                _ => panic!(
+                    "Got unknown parameter mode for opcode {} for pos {} ({})",
+                    raw_opcode,
+                    offset / 100,
+                    raw_mode
+                ),
+            }
+        }
+
+        fn parse_inst_addr(&mut self, param1_mode: ParamMode) -> usize {
+            self.get_addr(1, param1_mode)
+        }
+
+        fn parse_inst_data(&mut self, param1_mode: ParamMode) -> i64 {
+            let param1_addr = self.get_addr(1, param1_mode);
+            self.code[param1_addr]
+        }
+
+        fn parse_inst_data_data(
+            &mut self,
+            param1_mode: ParamMode,
+            param2_mode: ParamMode,
+        ) -> (i64, i64) {
+            let param1_addr = self.get_addr(1, param1_mode);
+            let param2_addr = self.get_addr(2, param2_mode);
+            (self.code[param1_addr], self.code[param2_addr])
+        }
+
+        fn parse_inst_data_data_addr(
+            &mut self,
+            param1_mode: ParamMode,
+            param2_mode: ParamMode,
+            param3_mode: ParamMode,
+        ) -> (i64, i64, usize) {
+            let param1_addr = self.get_addr(1, param1_mode);
+            let param2_addr = self.get_addr(2, param2_mode);
+            let param3_addr = self.get_addr(3, param3_mode);
+            (self.code[param1_addr], self.code[param2_addr], param3_addr)
+        }
+
+        fn get_addr(&mut self, offset: usize, mode: ParamMode) -> usize {
+            let addr = match mode {
+                ParamMode::Position => self.code[self.pc + offset] as usize,
+                ParamMode::Immediate => self.pc + offset,
+                ParamMode::Relative => (self.rel + self.code[self.pc + offset]) as usize,
+            };
+            self.ensure_capacity(addr);
+            addr
+        }
+
+        fn ensure_capacity(&mut self, addr: usize) {
+            if addr >= self.code.len() {
+                let additional = addr - self.code.len();
+                self.code.reserve(additional);
+                for _ in 0..=additional {
+                    self.code.push(0);
+                }
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum ParamMode {
+        Immediate,
+        Position,
+        Relative,
+    }
+
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub enum ProcessorState {
+        IoWait,
+        Halted,
+    }
+}
+
+/// What to run a workload on
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Engine {
+    /// The interpreter `Processor` replaced
+    Baseline,
+    /// `Processor` decoding every instruction as it is executed
+    Uncached,
+    /// `Processor` with its cache of decoded instructions
+    Cached,
+}
+
+fn load(path: &str) -> Vec<i64> {
+    parse_program(&std::fs::read_to_string(path).expect("Unable to open file"))
+        .expect("Unable to parse program")
+}
+
+/// The BOOST program in sensor boost mode, which runs for a few hundred thousand instructions
+fn boost(code: &[i64], engine: Engine) -> i64 {
+    if engine == Engine::Baseline {
+        let (state, output) = baseline::Processor::new(code.to_vec()).execute(vec![2].into());
+        assert_eq!(state, baseline::ProcessorState::Halted);
+        return output[0];
+    }
+
+    let mut proc = Processor::new(code.to_vec());
+    proc.set_decode_cache(engine == Engine::Cached);
+    let (state, output) = proc.execute(vec![2].into()).unwrap();
+    assert_eq!(state, ProcessorState::Halted);
+    output[0]
+}
+
+/// A full game of breakout, steering the paddle towards the ball, returning the final score
+fn breakout(code: &[i64], engine: Engine) -> i64 {
+    let mut code = code.to_vec();
+    code[0] = 2;
+    if engine == Engine::Baseline {
+        return baseline_breakout(code);
+    }
+
+    let mut proc = Processor::new(code);
+    proc.set_decode_cache(engine == Engine::Cached);
+
+    let ball = Cell::new(0i64);
+    let paddle = Cell::new(0);
+    let mut score = 0;
+    let mut pending = Vec::new();
+    let state = proc.run(
+        &mut InputFn(|| Some((ball.get() - paddle.get()).signum())),
+        &mut OutputFn(|value| {
+            pending.push(value);
+            if let [x, y, tile] = pending[..] {
+                match (x, y, tile) {
+                    (-1, 0, _) => score = tile,
+                    (_, _, 3) => paddle.set(x),
+                    (_, _, 4) => ball.set(x),
+                    _ => {}
+                }
+                pending.clear();
+            }
+        }),
+    );
+    assert_eq!(state, Ok(ProcessorState::Halted));
+    score
+}
+
+/// Breakout on the baseline interpreter, which is run until it waits for the joystick each time
+fn baseline_breakout(code: Vec<i64>) -> i64 {
+    let mut proc = baseline::Processor::new(code);
+    let (mut ball, mut paddle, mut score) = (0i64, 0, 0);
+    let mut input = Vec::new();
+    loop {
+        let (state, output) = proc.execute(input.into());
+        for frame in Vec::from(output).chunks(3) {
+            match *frame {
+                [-1, 0, tile] => score = tile,
+                [x, _, 3] => paddle = x,
+                [x, _, 4] => ball = x,
+                _ => {}
+            }
+        }
+        if state == baseline::ProcessorState::Halted {
+            return score;
+        }
+        input = vec![(ball - paddle).signum()];
+    }
+}
+
+/// Run `f` several times, printing the fastest time taken
+fn bench<F: FnMut() -> i64>(name: &str, mut f: F) -> Duration {
+    let result = f();
+    let best = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            assert_eq!(f(), result);
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+    println!("{:<36} {:>10.3} ms", name, best.as_secs_f64() * 1000.0);
+    best
+}
+
+/// A program to run on an engine, returning a value to check
+type Workload = fn(&[i64], Engine) -> i64;
+
+fn main() {
+    let day09 = load("src/bin/day09.txt");
+    let day13 = load("src/bin/day13.txt");
+    let workloads: [(&str, &[i64], Workload); 2] = [
+        ("day09 BOOST", &day09, boost),
+        ("day13 breakout", &day13, breakout),
+    ];
+
+    for (name, code, workload) in workloads.iter() {
+        let baseline = bench(&format!("{} (baseline)", name), || {
+            workload(code, Engine::Baseline)
+        });
+        let uncached = bench(&format!("{} (decoding every step)", name), || {
+            workload(code, Engine::Uncached)
+        });
+        let cached = bench(&format!("{} (decode cache)", name), || {
+            workload(code, Engine::Cached)
+        });
+        for (engine, time) in &[("decoding every step", uncached), ("decode cache", cached)] {
+            println!(
+                "{:<36} {:>10.2}x",
+                format!("speedup ({})", engine),
+                baseline.as_secs_f64() / time.as_secs_f64()
+            );
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Compiles the Intcode programs run by the tests of `intcode::compile` into `OUT_DIR`, so the
+//! generated code always matches the compiler that produced it.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[allow(dead_code, unused_imports)]
+#[path = "src/intcode/mod.rs"]
+mod intcode;
+
+const PROGRAMS: [(&str, &str); 4] = [
+    ("day05", "src/bin/day05.txt"),
+    ("day09", "src/bin/day09.txt"),
+    ("compare", "src/intcode/testdata/compare.txt"),
+    ("self_modifying", "src/intcode/testdata/self_modifying.txt"),
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/intcode");
+
+    let mut source = String::new();
+    for (name, path) in PROGRAMS.iter() {
+        println!("cargo:rerun-if-changed={}", path);
+        let text = fs::read_to_string(path).unwrap();
+        let code = intcode::parse_program(&text).unwrap();
+        source.push_str(&format!(
+            "pub mod {} {{\n{}}}\n\n",
+            name,
+            intcode::compile(&code, "crate::intcode")
+        ));
+    }
+
+    let out_dir = env::var_os("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("compiled.rs"), source).unwrap();
+}
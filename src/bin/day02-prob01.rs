@@ -59,6 +59,7 @@ fn run(mut code: Vec<u32>) -> Vec<u32> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use advent_2019::intcode::{Execution, Fuzzer, Memory, Opcode, ParamMode, ProcessorState};
 
     #[test]
     fn test1() {
@@ -90,4 +91,29 @@ mod test {
             vec![30, 1, 1, 4, 2, 5, 6, 0, 99]
         );
     }
+
+    #[test]
+    fn test_fuzz_against_processor() {
+        let mut fuzzer = Fuzzer::new(|code, input| Execution::processor::<i32>(code, input, true));
+        fuzzer.generator.opcodes = vec![Opcode::Add, Opcode::Mul];
+        fuzzer.generator.modes = vec![ParamMode::Position];
+        fuzzer.generator.values = 0..=9;
+        fuzzer.iterations = 300;
+        fuzzer.add("day02", |code, _| {
+            let memory = run(code.iter().map(|value| *value as u32).collect());
+            Ok(Execution {
+                state: ProcessorState::Halted,
+                output: vec![],
+                memory: Memory::new(
+                    &memory
+                        .into_iter()
+                        .map(|value| value as i64)
+                        .collect::<Vec<_>>(),
+                ),
+            })
+        });
+        if let Some(divergence) = fuzzer.run().unwrap() {
+            panic!("{}", divergence);
+        }
+    }
 }
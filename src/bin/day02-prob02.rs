@@ -1,85 +1,56 @@
+use advent_2019::intcode::{parse_program, GoalSeek, Target};
+
 fn main() {
     let result = std::fs::read_to_string("src/bin/day02.txt")
         .map(|file| {
-            let line = file
-                .lines()
-                .filter(|line| !line.is_empty())
-                .collect::<Vec<&str>>()[0];
-            let code = line
-                .split(',')
-                .map(|item| item.parse::<u32>().unwrap())
-                .collect::<Vec<u32>>();
+            let code = parse_program(&file).unwrap();
 
-            find_answer(code, 19_690_720)
+            find_answer(&code, 19_690_720)
         })
         .expect("Unable to open file");
 
     println!("{}", 100 * result.noun + result.verb);
 }
 
-fn find_answer(code: Vec<u32>, desired_result: u32) -> InitialState {
-    for noun in 0..99 {
-        for verb in 0..99 {
-            let iter_code = code.clone();
-            let init_state = InitialState { noun, verb };
-
-            let post = execute(iter_code, init_state.clone());
-            let result = post[0];
-
-            if result == desired_result {
-                return init_state;
-            }
-        }
+fn find_answer(code: &[i64], desired_result: i64) -> InitialState {
+    let solutions = noun_verb(0..=99, 0..=99)
+        .solve(code, Target::Memory(0), desired_result)
+        .expect("Too many nouns and verbs to search");
+    match solutions.first() {
+        Some(values) => InitialState {
+            noun: values[0],
+            verb: values[1],
+        },
+        None => panic!("Not able to find answer"),
     }
-    panic!("Not able to find answer")
 }
 
-#[derive(Debug, Clone)]
-struct InitialState {
-    pub noun: u32,
-    pub verb: u32,
+fn noun_verb(
+    nouns: std::ops::RangeInclusive<i64>,
+    verbs: std::ops::RangeInclusive<i64>,
+) -> GoalSeek {
+    GoalSeek::new(vec![(1, nouns), (2, verbs)])
 }
 
-fn execute(mut code: Vec<u32>, init_state: InitialState) -> Vec<u32> {
-    let mut i: usize = 0;
-    code[1] = init_state.noun;
-    code[2] = init_state.verb;
-
-    //    println!("{:?}", code);
-    while {
-        let opcode = code[i];
-        //        println!("opcode {} at {}", opcode, i);
-        let run_again = if opcode == 99 {
-            false
-        } else if opcode == 1 {
-            let arg1 = code[code[i + 1] as usize];
-            let arg2 = code[code[i + 2] as usize];
-            let out_addr = code[i + 3] as usize;
-            code[out_addr] = arg1 + arg2;
-            true
-        } else if opcode == 2 {
-            let arg1 = code[code[i + 1] as usize];
-            let arg2 = code[code[i + 2] as usize];
-            let out_addr = code[i + 3] as usize;
-            code[out_addr] = arg1 * arg2;
-            true
-        } else {
-            panic!("Unexpected opcode {} at {}", opcode, i)
-        };
-        //        println!("{:?}", code);
-
-        i += 4;
-
-        run_again
-    } {}
-
-    code
+#[derive(Debug, Clone)]
+struct InitialState {
+    pub noun: i64,
+    pub verb: i64,
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    fn execute(code: Vec<i64>, init_state: InitialState) -> Vec<i64> {
+        let InitialState { noun, verb } = init_state;
+        noun_verb(noun..=noun, verb..=verb)
+            .evaluate(&code, &[noun, verb])
+            .unwrap()
+            .memory
+            .to_vec()
+    }
+
     #[test]
     fn test1() {
         assert_eq!(
@@ -125,4 +96,12 @@ mod test {
             vec![30, 1, 1, 4, 2, 5, 6, 0, 99]
         );
     }
+
+    #[test]
+    fn test_last_noun_and_verb_searched() {
+        // Sets [0] to the sum of the noun and verb themselves
+        let code = vec![1101, 0, 0, 0, 99];
+        let answer = find_answer(&code, 198);
+        assert_eq!((answer.noun, answer.verb), (99, 99));
+    }
 }
@@ -114,6 +114,7 @@ fn get_val(code: &[i32], i: usize, is_imm: bool) -> i32 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use advent_2019::intcode::{Execution, Fuzzer, Memory, Opcode, ParamMode, ProcessorState};
 
     #[test]
     fn test1() {
@@ -218,4 +219,32 @@ mod test {
             (vec![1002, 5, 1, 7, 99, 4, 5, 4], vec![])
         )
     }
+
+    #[test]
+    fn test_fuzz_against_processor() {
+        let mut fuzzer = Fuzzer::new(|code, input| Execution::processor::<i32>(code, input, true));
+        fuzzer.generator.opcodes = vec![Opcode::Add, Opcode::Mul, Opcode::Read, Opcode::Output];
+        fuzzer.generator.modes = vec![ParamMode::Position, ParamMode::Immediate];
+        fuzzer.iterations = 300;
+        fuzzer.add("day05", |code, input| {
+            let (memory, output) = execute(
+                code.iter().map(|value| *value as i32).collect(),
+                None,
+                input.iter().map(|value| *value as i32).collect(),
+            );
+            Ok(Execution {
+                state: ProcessorState::Halted,
+                output: output.into_iter().map(|(_, value)| value as i64).collect(),
+                memory: Memory::new(
+                    &memory
+                        .into_iter()
+                        .map(|value| value as i64)
+                        .collect::<Vec<_>>(),
+                ),
+            })
+        });
+        if let Some(divergence) = fuzzer.run().unwrap() {
+            panic!("{}", divergence);
+        }
+    }
 }
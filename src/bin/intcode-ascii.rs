@@ -0,0 +1,27 @@
+use advent_2019::intcode::{parse_program, AsciiTerminal};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: intcode-ascii <program> [script]");
+        std::process::exit(1);
+    }
+
+    let code = std::fs::read_to_string(&args[0])
+        .map(|file| parse_program(&file).expect("Unable to parse program"))
+        .expect("Unable to open file");
+
+    let mut terminal = AsciiTerminal::new(code);
+    if let Some(script) = args.get(1) {
+        let script = std::fs::read_to_string(script).expect("Unable to open script");
+        for line in script.lines() {
+            terminal.input.push_line(line);
+        }
+    }
+
+    let stdin = std::io::stdin();
+    let state = terminal
+        .interact(stdin.lock(), std::io::stdout())
+        .expect("Program failed");
+    eprintln!("Stopped in state {:?}", state);
+}
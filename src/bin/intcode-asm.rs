@@ -0,0 +1,25 @@
+use advent_2019::intcode::assemble;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("Usage: intcode-asm <source>");
+        std::process::exit(1);
+    }
+
+    let result = std::fs::read_to_string(&args[0])
+        .map(|file| match assemble(&file) {
+            Ok(code) => code
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        })
+        .expect("Unable to open file");
+
+    println!("{}", result);
+}
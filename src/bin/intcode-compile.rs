@@ -0,0 +1,19 @@
+use advent_2019::intcode::{compile, parse_program};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: intcode-compile <program> [module path]");
+        std::process::exit(1);
+    }
+
+    let code = std::fs::read_to_string(&args[0])
+        .map(|file| parse_program(&file).expect("Unable to parse program"))
+        .expect("Unable to open file");
+
+    let intcode = args
+        .get(1)
+        .map(String::as_str)
+        .unwrap_or("advent_2019::intcode");
+    print!("{}", compile(&code, intcode));
+}
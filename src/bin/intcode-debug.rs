@@ -0,0 +1,31 @@
+use std::io::{BufRead, Write};
+
+use advent_2019::intcode::{parse_program, Debugger};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("Usage: intcode-debug <program>");
+        std::process::exit(1);
+    }
+
+    let mut debugger = std::fs::read_to_string(&args[0])
+        .map(|file| Debugger::new(parse_program(&file).expect("Unable to parse program")))
+        .expect("Unable to open file");
+
+    println!("{}", debugger.command("list").unwrap());
+    let stdin = std::io::stdin();
+    loop {
+        print!("(icdb) ");
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        match debugger.command(&line) {
+            Some(result) => println!("{}", result),
+            None => break,
+        }
+    }
+}
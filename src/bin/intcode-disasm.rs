@@ -0,0 +1,26 @@
+use advent_2019::intcode::{parse_program, Disassembly};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: intcode-disasm <program> [entry point...]");
+        std::process::exit(1);
+    }
+
+    let entry_points = std::iter::once(0)
+        .chain(
+            args[1..]
+                .iter()
+                .map(|arg| arg.parse::<usize>().expect("Invalid entry point")),
+        )
+        .collect::<Vec<usize>>();
+
+    let result = std::fs::read_to_string(&args[0])
+        .map(|file| {
+            let code = parse_program(&file).expect("Unable to parse program");
+            Disassembly::with_entry_points(&code, &entry_points)
+        })
+        .expect("Unable to open file");
+
+    print!("{}", result);
+}
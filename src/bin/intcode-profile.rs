@@ -0,0 +1,27 @@
+use advent_2019::intcode::{parse_program, Processor, Profiler};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("Usage: intcode-profile <program> [input...]");
+        std::process::exit(1);
+    }
+
+    let input = args[1..]
+        .iter()
+        .map(|arg| arg.parse::<i64>().expect("Invalid input"))
+        .collect();
+
+    let code = std::fs::read_to_string(&args[0])
+        .map(|file| parse_program(&file).expect("Unable to parse program"))
+        .expect("Unable to open file");
+
+    let mut profiler = Profiler::new();
+    let (state, output) = Processor::new(code.clone())
+        .execute_observed(input, &mut profiler)
+        .expect("Program failed");
+
+    println!("Stopped in state {:?} with output {:?}\n", state, output);
+    println!("{}", profiler.report());
+    print!("{}", profiler.annotate(&code));
+}
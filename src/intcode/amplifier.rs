@@ -0,0 +1,213 @@
+use itertools::Itertools;
+
+use super::{Cell, Network, NetworkError, NetworkState, Topology};
+
+/// The phase settings that gave the strongest signal, found by `PhaseSearch::run`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PhaseSetting<C: Cell = i64> {
+    /// The phase given to each amplifier, in order
+    pub phases: Vec<C>,
+    /// The signal the last amplifier sent to the thrusters
+    pub signal: C,
+}
+
+/// How a search over phase settings should be carried out
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PhaseSearch<C: Cell = i64> {
+    /// The values phases may take, each of which is used at most once per setting
+    pub phases: Vec<C>,
+    /// How many amplifiers to chain together
+    pub amplifiers: usize,
+    /// Whether the last amplifier feeds back into the first, as in a feedback loop
+    pub feedback: bool,
+    /// How many threads to spread the settings across
+    pub threads: usize,
+}
+
+impl<C: Cell> PhaseSearch<C> {
+    /// A search with one amplifier per phase value, using every available core
+    pub fn new(phases: Vec<C>, feedback: bool) -> PhaseSearch<C> {
+        PhaseSearch {
+            amplifiers: phases.len(),
+            phases,
+            feedback,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// The signal produced when the amplifiers running `code` are given `phases`, starting from
+    /// an input signal of 0. Settings that never halt or never produce a signal give `None`.
+    pub fn signal(&self, code: &[C], phases: &[C]) -> Result<Option<C>, NetworkError> {
+        let topology = if self.feedback {
+            Topology::Ring(phases.len())
+        } else {
+            Topology::Chain(phases.len())
+        };
+        // Only an empty topology can be invalid, and no amplifiers give no signal
+        let mut network = match Network::new(code, &topology) {
+            Ok(network) => network,
+            Err(_) => return Ok(None),
+        };
+        for (node, phase) in phases.iter().enumerate() {
+            network.feed(node, Some(phase.clone()))?;
+        }
+        network.feed(0, Some(C::zero()))?;
+
+        let outcome = network.run()?;
+        Ok(match outcome.state {
+            NetworkState::Halted => outcome.result().cloned(),
+            _ => None,
+        })
+    }
+}
+
+impl<C: Cell + Send + Sync> PhaseSearch<C> {
+    /// Try every ordering of the phase values across the amplifiers running `code`, returning the
+    /// one giving the strongest signal, or `None` if none of them produced one. Ties go to the
+    /// setting that comes first in the order the phases were listed, so the answer does not
+    /// depend on how the work was split between threads.
+    pub fn run(&self, code: &[C]) -> Result<Option<PhaseSetting<C>>, NetworkError> {
+        let threads = self.threads.max(1);
+        let results = std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|worker| scope.spawn(move || self.run_share(code, worker, threads)))
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect::<Result<Vec<_>, NetworkError>>()
+        })?;
+
+        Ok(results
+            .into_iter()
+            .flatten()
+            .fold(None, PhaseSearch::keep_best)
+            .map(|(_, setting)| setting))
+    }
+
+    /// Evaluate every `threads`th setting starting from `worker`, returning the best along with
+    /// its position in the full order of settings
+    fn run_share(
+        &self,
+        code: &[C],
+        worker: usize,
+        threads: usize,
+    ) -> Result<Option<(usize, PhaseSetting<C>)>, NetworkError> {
+        let mut best: Option<(usize, PhaseSetting<C>)> = None;
+        for (index, phases) in (0..self.phases.len())
+            .permutations(self.amplifiers)
+            .enumerate()
+            .skip(worker)
+            .step_by(threads)
+        {
+            let phases = phases
+                .into_iter()
+                .map(|i| self.phases[i].clone())
+                .collect::<Vec<C>>();
+            if let Some(signal) = self.signal(code, &phases)? {
+                best = PhaseSearch::keep_best(best, (index, PhaseSetting { phases, signal }));
+            }
+        }
+        Ok(best)
+    }
+
+    /// Whichever of the best setting so far and `candidate` has the stronger signal, or comes
+    /// first if they are tied
+    fn keep_best(
+        best: Option<(usize, PhaseSetting<C>)>,
+        candidate: (usize, PhaseSetting<C>),
+    ) -> Option<(usize, PhaseSetting<C>)> {
+        match best {
+            Some(best)
+                if best.1.signal > candidate.1.signal
+                    || (best.1.signal == candidate.1.signal && best.0 < candidate.0) =>
+            {
+                Some(best)
+            }
+            _ => Some(candidate),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<i64> {
+        crate::intcode::parse_program(text).unwrap()
+    }
+
+    #[test]
+    fn test_chain() {
+        let code = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        assert_eq!(
+            PhaseSearch::new(vec![0, 1, 2, 3, 4], false).run(&code),
+            Ok(Some(PhaseSetting {
+                phases: vec![4, 3, 2, 1, 0],
+                signal: 43210
+            }))
+        );
+    }
+
+    #[test]
+    fn test_no_amplifiers() {
+        let code = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        assert_eq!(PhaseSearch::new(vec![], true).signal(&code, &[]), Ok(None));
+    }
+
+    #[test]
+    fn test_feedback() {
+        let code = parse(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        );
+        assert_eq!(
+            PhaseSearch::new(vec![5, 6, 7, 8, 9], true).run(&code),
+            Ok(Some(PhaseSetting {
+                phases: vec![9, 8, 7, 6, 5],
+                signal: 139629729
+            }))
+        );
+    }
+
+    #[test]
+    fn test_result_independent_of_threads() {
+        let code = parse("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
+        let mut search = PhaseSearch::new(vec![0, 1, 2, 3, 4, 5, 6], false);
+        search.amplifiers = 4;
+        let results = (1..=5)
+            .map(|threads| {
+                search.threads = threads;
+                search.run(&code).unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(results.iter().all(|result| *result == results[0]));
+        assert_eq!(
+            results[0],
+            Some(PhaseSetting {
+                phases: vec![6, 5, 4, 3],
+                signal: 6543
+            })
+        );
+    }
+
+    #[test]
+    fn test_ties_go_to_first_setting() {
+        // Ignores its phase and outputs its input signal plus one
+        let code = parse("3,11,3,12,101,1,12,12,4,12,99,0,0");
+        let mut search = PhaseSearch::new(vec![3, 1, 2], false);
+        search.threads = 2;
+        assert_eq!(
+            search.run(&code),
+            Ok(Some(PhaseSetting {
+                phases: vec![3, 1, 2],
+                signal: 3
+            }))
+        );
+    }
+
+    #[test]
+    fn test_no_signal() {
+        let code = parse("3,0,3,0,99");
+        assert_eq!(PhaseSearch::new(vec![0, 1], false).run(&code), Ok(None));
+    }
+}
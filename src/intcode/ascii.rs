@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, Write};
+
+use super::{Input, IntcodeError, Output, OutputFn, Processor, ProcessorState};
+
+/// The largest value that is treated as a character rather than a number
+const ASCII_MAX: i64 = 127;
+
+/// The values to feed a program to have it read `text`, one per character
+pub fn encode_ascii(text: &str) -> Vec<i64> {
+    text.chars().map(|c| c as i64).collect()
+}
+
+/// Part of the output of a text-driven program
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AsciiChunk {
+    /// A run of ASCII characters
+    Text(String),
+    /// A value outside the ASCII range, which programs use to report answers too large to fit
+    /// in a character
+    Value(i64),
+}
+
+/// Collects output from a program, decoding values in the ASCII range as text and passing
+/// anything else through as a number
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AsciiOutput {
+    pub chunks: Vec<AsciiChunk>,
+}
+
+impl AsciiOutput {
+    pub fn new() -> AsciiOutput {
+        AsciiOutput::default()
+    }
+
+    pub fn decode(values: &[i64]) -> AsciiOutput {
+        let mut output = AsciiOutput::new();
+        for value in values {
+            output.write(*value);
+        }
+        output
+    }
+
+    /// All of the text, leaving out any values outside the ASCII range
+    pub fn text(&self) -> String {
+        self.chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                AsciiChunk::Text(text) => Some(text.as_str()),
+                AsciiChunk::Value(_) => None,
+            })
+            .collect()
+    }
+
+    /// The values outside the ASCII range, in the order they were output
+    pub fn values(&self) -> Vec<i64> {
+        self.chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                AsciiChunk::Text(_) => None,
+                AsciiChunk::Value(value) => Some(*value),
+            })
+            .collect()
+    }
+}
+
+impl Output<i64> for AsciiOutput {
+    fn write(&mut self, value: i64) {
+        if !(0..=ASCII_MAX).contains(&value) {
+            self.chunks.push(AsciiChunk::Value(value));
+            return;
+        }
+        let c = value as u8 as char;
+        match self.chunks.last_mut() {
+            Some(AsciiChunk::Text(text)) => text.push(c),
+            _ => self.chunks.push(AsciiChunk::Text(c.to_string())),
+        }
+    }
+}
+
+/// Values outside the ASCII range are shown as numbers on a line of their own
+impl Display for AsciiOutput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for chunk in &self.chunks {
+            match chunk {
+                AsciiChunk::Text(text) => write!(f, "{}", text)?,
+                AsciiChunk::Value(value) => writeln!(f, "{}", value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Input for a text-driven program, supplied a line at a time
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct AsciiInput {
+    pending: VecDeque<i64>,
+}
+
+impl AsciiInput {
+    pub fn new() -> AsciiInput {
+        AsciiInput::default()
+    }
+
+    /// Queue a line of text. Any line ending it already has is replaced with a single newline,
+    /// which is what Intcode programs expect.
+    pub fn push_line(&mut self, line: &str) {
+        self.pending
+            .extend(encode_ascii(line.trim_end_matches(&['\r', '\n'][..])));
+        self.pending.push_back('\n' as i64);
+    }
+
+    /// Queue a raw value, which need not be a character
+    pub fn push_value(&mut self, value: i64) {
+        self.pending.push_back(value);
+    }
+
+    /// Whether every queued value has been read
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Input<i64> for AsciiInput {
+    fn read(&mut self) -> Option<i64> {
+        self.pending.pop_front()
+    }
+}
+
+/// An error while running a program interactively
+#[derive(Debug)]
+pub enum TerminalError {
+    Io(std::io::Error),
+    Intcode(IntcodeError),
+}
+
+impl Display for TerminalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TerminalError::Io(err) => write!(f, "{}", err),
+            TerminalError::Intcode(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for TerminalError {}
+
+/// Runs a program that talks in text, either scripted a line at a time or interactively
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AsciiTerminal {
+    pub processor: Processor,
+    pub input: AsciiInput,
+}
+
+impl AsciiTerminal {
+    pub fn new(code: Vec<i64>) -> AsciiTerminal {
+        AsciiTerminal {
+            processor: Processor::new(code),
+            input: AsciiInput::new(),
+        }
+    }
+
+    /// Run until the program halts or wants more input than has been queued, returning what it
+    /// printed along the way
+    pub fn run(&mut self) -> Result<(ProcessorState, AsciiOutput), IntcodeError> {
+        let mut output = AsciiOutput::new();
+        let state = self.processor.run(&mut self.input, &mut output)?;
+        Ok((state, output))
+    }
+
+    /// Queue a line of input and run until it has been dealt with
+    pub fn send_line(&mut self, line: &str) -> Result<(ProcessorState, AsciiOutput), IntcodeError> {
+        self.input.push_line(line);
+        self.run()
+    }
+
+    /// Connect the program to a terminal. Output is written a line at a time, along with any
+    /// partial line such as a prompt whenever the program waits for input, and each line read
+    /// from `reader` is only passed on once it is complete. Returns when the program halts, or
+    /// with `ProcessorState::IoWait` once `reader` runs out.
+    pub fn interact<R: BufRead, W: Write>(
+        &mut self,
+        mut reader: R,
+        mut writer: W,
+    ) -> Result<ProcessorState, TerminalError> {
+        loop {
+            let mut line = String::new();
+            let mut error = None;
+            let state = {
+                let mut flush_line = |line: &mut String| {
+                    if error.is_none() {
+                        error = writer
+                            .write_all(line.as_bytes())
+                            .and_then(|_| writer.flush())
+                            .err();
+                    }
+                    line.clear();
+                };
+                let mut output = OutputFn(|value: i64| {
+                    if (0..=ASCII_MAX).contains(&value) {
+                        line.push(value as u8 as char);
+                        if value == '\n' as i64 {
+                            flush_line(&mut line);
+                        }
+                    } else {
+                        flush_line(&mut line);
+                        flush_line(&mut format!("{}\n", value));
+                    }
+                });
+                let state = self
+                    .processor
+                    .run(&mut self.input, &mut output)
+                    .map_err(TerminalError::Intcode)?;
+                flush_line(&mut line);
+                state
+            };
+            if let Some(err) = error {
+                return Err(TerminalError::Io(err));
+            }
+
+            if state != ProcessorState::IoWait {
+                return Ok(state);
+            }
+            let mut line = String::new();
+            if reader.read_line(&mut line).map_err(TerminalError::Io)? == 0 {
+                return Ok(state);
+            }
+            self.input.push_line(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Echoes every character it reads, forever
+    fn echo() -> Vec<i64> {
+        vec![3, 8, 4, 8, 1105, 1, 0, 0, 0]
+    }
+
+    #[test]
+    fn test_decode() {
+        let output = AsciiOutput::decode(&[72, 105, 10, 1000, -1, 10, 65]);
+        assert_eq!(
+            output.chunks,
+            vec![
+                AsciiChunk::Text("Hi\n".to_string()),
+                AsciiChunk::Value(1000),
+                AsciiChunk::Value(-1),
+                AsciiChunk::Text("\nA".to_string()),
+            ]
+        );
+        assert_eq!(output.text(), "Hi\n\nA");
+        assert_eq!(output.values(), vec![1000, -1]);
+        assert_eq!(output.to_string(), "Hi\n1000\n-1\n\nA");
+    }
+
+    #[test]
+    fn test_send_line() {
+        let mut terminal = AsciiTerminal::new(echo());
+        let (state, output) = terminal.send_line("hello\r\n").unwrap();
+        assert_eq!(state, ProcessorState::IoWait);
+        assert_eq!(output.text(), "hello\n");
+        assert!(terminal.input.is_empty());
+    }
+
+    #[test]
+    fn test_interact() {
+        let mut terminal = AsciiTerminal::new(echo());
+        let mut screen = Vec::new();
+        let state = terminal
+            .interact("one\ntwo".as_bytes(), &mut screen)
+            .unwrap();
+        assert_eq!(state, ProcessorState::IoWait);
+        assert_eq!(String::from_utf8(screen).unwrap(), "one\ntwo\n");
+    }
+
+    #[test]
+    fn test_interact_prompt_and_answer() {
+        // Prompts with "? ", reads one character, then reports 1000 and halts
+        let code = vec![104, 63, 104, 32, 3, 11, 104, 1000, 99, 0, 0, 0];
+        let mut screen = Vec::new();
+        let state = AsciiTerminal::new(code)
+            .interact("y\n".as_bytes(), &mut screen)
+            .unwrap();
+        assert_eq!(state, ProcessorState::Halted);
+        assert_eq!(String::from_utf8(screen).unwrap(), "? 1000\n");
+    }
+}
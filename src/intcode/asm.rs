@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use super::{Opcode, ParamMode};
+
+/// The ways in which assembling a program can fail, each tagged with the (1-based) source line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AsmError {
+    UnknownMnemonic {
+        line: usize,
+        mnemonic: String,
+    },
+    WrongOperandCount {
+        line: usize,
+        mnemonic: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidOperand {
+        line: usize,
+        operand: String,
+    },
+    UndefinedLabel {
+        line: usize,
+        label: String,
+    },
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+    /// An operand or data value does not fit in an `i64`
+    Overflow {
+        line: usize,
+    },
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmError::UnknownMnemonic { line, mnemonic } => {
+                write!(f, "Line {}: unknown mnemonic '{}'", line, mnemonic)
+            }
+            AsmError::WrongOperandCount {
+                line,
+                mnemonic,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Line {}: '{}' takes {} operands but got {}",
+                line, mnemonic, expected, found
+            ),
+            AsmError::InvalidOperand { line, operand } => {
+                write!(f, "Line {}: invalid operand '{}'", line, operand)
+            }
+            AsmError::UndefinedLabel { line, label } => {
+                write!(f, "Line {}: undefined label '{}'", line, label)
+            }
+            AsmError::DuplicateLabel { line, label } => {
+                write!(f, "Line {}: label '{}' is already defined", line, label)
+            }
+            AsmError::Overflow { line } => write!(f, "Line {}: value out of range", line),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+/// A sum of integer literals and label references, resolved once all labels are known
+#[derive(Debug, Clone)]
+struct Expr {
+    terms: Vec<(i64, Term)>,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Literal(i64),
+    Label(String),
+}
+
+impl Expr {
+    /// Parse `[+|-] term ((+|-) term)*`, where each term is an integer or a label
+    fn parse(text: &str) -> Option<Expr> {
+        let mut tokens: Vec<String> = Vec::new();
+        for c in text.chars() {
+            if c == '+' || c == '-' {
+                tokens.push(c.to_string());
+            } else if c.is_whitespace() {
+                tokens.push(String::new());
+            } else {
+                match tokens.last_mut() {
+                    Some(token) if token != "+" && token != "-" => token.push(c),
+                    _ => tokens.push(c.to_string()),
+                }
+            }
+        }
+        tokens.retain(|token| !token.is_empty());
+
+        let mut terms: Vec<(i64, Term)> = Vec::new();
+        let mut tokens = tokens.iter().peekable();
+        let mut sign = match tokens.peek().map(|token| token.as_str()) {
+            Some("-") => {
+                tokens.next();
+                -1
+            }
+            Some("+") => {
+                tokens.next();
+                1
+            }
+            _ => 1,
+        };
+        loop {
+            terms.push((sign, Expr::parse_term(tokens.next()?)?));
+            sign = match tokens.next().map(|token| token.as_str()) {
+                None => return Some(Expr { terms }),
+                Some("+") => 1,
+                Some("-") => -1,
+                Some(_) => return None,
+            };
+        }
+    }
+
+    fn parse_term(text: &str) -> Option<Term> {
+        if let Ok(value) = text.parse::<i64>() {
+            Some(Term::Literal(value))
+        } else if is_label(text) {
+            Some(Term::Label(text.to_string()))
+        } else {
+            None
+        }
+    }
+
+    fn resolve(&self, line: usize, labels: &HashMap<String, usize>) -> Result<i64, AsmError> {
+        self.terms.iter().try_fold(0, |acc, (sign, term)| {
+            let value = match term {
+                Term::Literal(value) => *value,
+                Term::Label(label) => {
+                    *labels.get(label).ok_or_else(|| AsmError::UndefinedLabel {
+                        line,
+                        label: label.clone(),
+                    })? as i64
+                }
+            };
+            sign.checked_mul(value)
+                .and_then(|value| value.checked_add(acc))
+                .ok_or(AsmError::Overflow { line })
+        })
+    }
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    text != "rb"
+        && matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// One unit of output that occupies memory, pending label resolution
+#[derive(Debug, Clone)]
+enum Item {
+    Instruction {
+        opcode: Opcode,
+        params: Vec<(ParamMode, Expr)>,
+    },
+    Data(Vec<Expr>),
+}
+
+impl Item {
+    fn size(&self) -> usize {
+        match self {
+            Item::Instruction { params, .. } => 1 + params.len(),
+            Item::Data(values) => values.len(),
+        }
+    }
+}
+
+/// Assemble a program written in the syntax produced by the disassembler into an image that can
+/// be loaded by `Processor::new`.
+///
+/// Each line holds an optional `label:` (or a numeric `addr:` column, which is ignored), then
+/// either an instruction or a directive, then an optional `;` comment. Instruction operands must
+/// state their mode explicitly: `[addr]` for position, `#value` for immediate and `rb+offset` for
+/// relative. Operands and data values may be integers, labels or sums of the two, such as
+/// `[buffer+2]`. The directives are `db value, ...` to emit values (or a `"string"` as its
+/// character codes) and `ds count` to reserve `count` zeroed cells.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels: HashMap<String, usize> = HashMap::new();
+    let mut items: Vec<(usize, Item)> = Vec::new();
+    let mut addr = 0;
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let line_num = i + 1;
+        let mut line = strip_comment(raw_line).trim();
+
+        if let Some(colon) = line.find(':') {
+            let label = line[..colon].trim();
+            if is_label(label) {
+                if labels.insert(label.to_string(), addr).is_some() {
+                    return Err(AsmError::DuplicateLabel {
+                        line: line_num,
+                        label: label.to_string(),
+                    });
+                }
+                line = line[colon + 1..].trim();
+            } else if label.parse::<usize>().is_ok() {
+                // an address column from a disassembly listing
+                line = line[colon + 1..].trim();
+            }
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, rest) = match line.find(char::is_whitespace) {
+            Some(split) => (&line[..split], line[split..].trim()),
+            None => (line, ""),
+        };
+        let item = parse_item(line_num, mnemonic, rest)?;
+        addr += item.size();
+        items.push((line_num, item));
+    }
+
+    let mut code: Vec<i64> = Vec::with_capacity(addr);
+    for (line_num, item) in items {
+        match item {
+            Item::Instruction { opcode, params } => {
+                let modes = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (mode, _))| mode_digit(*mode) * 10i64.pow(i as u32 + 2))
+                    .sum::<i64>();
+                code.push(modes + opcode.code());
+                for (_, expr) in params {
+                    code.push(expr.resolve(line_num, &labels)?);
+                }
+            }
+            Item::Data(values) => {
+                for expr in values {
+                    code.push(expr.resolve(line_num, &labels)?);
+                }
+            }
+        }
+    }
+    Ok(code)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn mode_digit(mode: ParamMode) -> i64 {
+    match mode {
+        ParamMode::Position => 0,
+        ParamMode::Immediate => 1,
+        ParamMode::Relative => 2,
+    }
+}
+
+fn parse_item(line: usize, mnemonic: &str, rest: &str) -> Result<Item, AsmError> {
+    let operands = split_operands(rest);
+    match mnemonic {
+        "db" => operands
+            .iter()
+            .map(|operand| parse_data(line, operand))
+            .collect::<Result<Vec<Vec<Expr>>, AsmError>>()
+            .map(|values| Item::Data(values.into_iter().flatten().collect())),
+        "ds" => {
+            let count = match operands.as_slice() {
+                [count] => count.parse::<usize>().ok(),
+                _ => None,
+            }
+            .ok_or_else(|| AsmError::InvalidOperand {
+                line,
+                operand: rest.to_string(),
+            })?;
+            Ok(Item::Data(vec![
+                Expr {
+                    terms: vec![(1, Term::Literal(0))]
+                };
+                count
+            ]))
+        }
+        _ => {
+            let opcode =
+                Opcode::from_mnemonic(mnemonic).ok_or_else(|| AsmError::UnknownMnemonic {
+                    line,
+                    mnemonic: mnemonic.to_string(),
+                })?;
+            if operands.len() != opcode.num_params() {
+                return Err(AsmError::WrongOperandCount {
+                    line,
+                    mnemonic: mnemonic.to_string(),
+                    expected: opcode.num_params(),
+                    found: operands.len(),
+                });
+            }
+            let params = operands
+                .iter()
+                .map(|operand| {
+                    parse_operand(operand).ok_or_else(|| AsmError::InvalidOperand {
+                        line,
+                        operand: operand.to_string(),
+                    })
+                })
+                .collect::<Result<Vec<(ParamMode, Expr)>, AsmError>>()?;
+            Ok(Item::Instruction { opcode, params })
+        }
+    }
+}
+
+fn split_operands(text: &str) -> Vec<String> {
+    if text.is_empty() {
+        return vec![];
+    }
+    let mut operands: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+    for c in text.chars() {
+        match c {
+            ',' if !in_string => {
+                operands.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => {
+                if c == '"' {
+                    in_string = !in_string;
+                }
+                current.push(c);
+            }
+        }
+    }
+    operands.push(current.trim().to_string());
+    operands
+}
+
+fn parse_operand(text: &str) -> Option<(ParamMode, Expr)> {
+    if text.starts_with('[') && text.ends_with(']') {
+        Some((ParamMode::Position, Expr::parse(&text[1..text.len() - 1])?))
+    } else if let Some(value) = text.strip_prefix('#') {
+        Some((ParamMode::Immediate, Expr::parse(value)?))
+    } else if let Some(offset) = text.strip_prefix("rb") {
+        let offset = offset.trim();
+        if offset.is_empty() {
+            Some((ParamMode::Relative, Expr::parse("0")?))
+        } else if offset.starts_with('+') || offset.starts_with('-') {
+            Some((ParamMode::Relative, Expr::parse(offset)?))
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+fn parse_data(line: usize, text: &str) -> Result<Vec<Expr>, AsmError> {
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        Ok(text[1..text.len() - 1]
+            .chars()
+            .map(|c| Expr {
+                terms: vec![(1, Term::Literal(c as i64))],
+            })
+            .collect())
+    } else {
+        Expr::parse(text)
+            .map(|expr| vec![expr])
+            .ok_or_else(|| AsmError::InvalidOperand {
+                line,
+                operand: text.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{parse_program, Disassembly, Processor, ProcessorState};
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(
+            assemble("mul [4], #3, [4]\nhalt"),
+            Ok(vec![1002, 4, 3, 4, 99])
+        )
+    }
+
+    #[test]
+    fn test_relative() {
+        assert_eq!(
+            assemble("rel #1\noutput rb-1\nread rb\nadd rb+2, #1, rb+3\nhalt"),
+            Ok(vec![109, 1, 204, -1, 203, 0, 21201, 2, 1, 3, 99])
+        )
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        assert_eq!(
+            assemble(
+                "
+                ; count down from 3, outputting each value
+                loop:   output [counter]
+                        add [counter], #-1, [counter]
+                        jnz [counter], #loop
+                        halt
+                counter: db 3
+                "
+            ),
+            Ok(vec![4, 10, 1001, 10, -1, 10, 1005, 10, 0, 99, 3])
+        )
+    }
+
+    #[test]
+    fn test_label_offsets() {
+        assert_eq!(
+            assemble("output [table+1]\nhalt\ntable: db 10, 20, table-1"),
+            Ok(vec![4, 4, 99, 10, 20, 2])
+        )
+    }
+
+    #[test]
+    fn test_string_and_space() {
+        assert_eq!(
+            assemble("db \"Hi; there\", 10 ; greeting\nds 2"),
+            Ok(vec![72, 105, 59, 32, 116, 104, 101, 114, 101, 10, 0, 0])
+        )
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            assemble("halt\nfoo #1"),
+            Err(AsmError::UnknownMnemonic {
+                line: 2,
+                mnemonic: "foo".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("add #1, #2"),
+            Err(AsmError::WrongOperandCount {
+                line: 1,
+                mnemonic: "add".to_string(),
+                expected: 3,
+                found: 2
+            })
+        );
+        assert_eq!(
+            assemble("output 5"),
+            Err(AsmError::InvalidOperand {
+                line: 1,
+                operand: "5".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("output [nowhere]"),
+            Err(AsmError::UndefinedLabel {
+                line: 1,
+                label: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("a: halt\na: halt"),
+            Err(AsmError::DuplicateLabel {
+                line: 2,
+                label: "a".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("halt\ndb 9223372036854775807+1"),
+            Err(AsmError::Overflow { line: 2 })
+        );
+    }
+
+    #[test]
+    fn test_execute_assembled() {
+        let code = assemble(
+            "
+                    read [n]
+            loop:   mul [acc], [n], [acc]
+                    add [n], #-1, [n]
+                    jnz [n], #loop
+                    output [acc]
+                    halt
+            n:      db 0
+            acc:    db 1
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            Processor::new(code).execute(deque!(5)).unwrap(),
+            (ProcessorState::Halted, deque!(120))
+        )
+    }
+
+    #[test]
+    fn test_round_trip_day09() {
+        let code = parse_program(&std::fs::read_to_string("src/bin/day09.txt").unwrap()).unwrap();
+        assert_eq!(assemble(&Disassembly::new(&code).source()), Ok(code))
+    }
+
+    #[test]
+    fn test_round_trip_listing() {
+        let code = vec![1105, 1, 5, 42, 43, 204, -1, 99];
+        assert_eq!(assemble(&Disassembly::new(&code).to_string()), Ok(code))
+    }
+
+    #[test]
+    fn test_round_trip_opcode_encodings() {
+        // Every opcode with every combination of mode digits, including digits for parameters
+        // it does not have
+        for opcode in &[1, 2, 3, 4, 5, 6, 7, 8, 9, 99] {
+            for modes in 0..3i64.pow(4) {
+                let raw = (0..4).fold(*opcode, |raw, digit| {
+                    raw + modes / 3i64.pow(digit) % 3 * 10i64.pow(digit + 2)
+                });
+                let code = vec![raw, 5, 6, 7, 99];
+                assert_eq!(
+                    assemble(&Disassembly::new(&code).source()),
+                    Ok(code.clone()),
+                    "{}",
+                    raw
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_day13() {
+        let code = parse_program(&std::fs::read_to_string("src/bin/day13.txt").unwrap()).unwrap();
+        assert_eq!(assemble(&Disassembly::new(&code).source()), Ok(code))
+    }
+}
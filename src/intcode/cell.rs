@@ -0,0 +1,118 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use num::{BigInt, ToPrimitive};
+
+/// A value that can be held in a single Intcode memory cell.
+///
+/// Arithmetic is checked: `Processor` reports `IntcodeError::Overflow` rather than wrapping when
+/// a result does not fit. `i32` and `i64` give fixed-width machines, while `BigInt` gives one
+/// that never overflows.
+pub trait Cell: Clone + Eq + Ord + Debug + Display + FromStr {
+    fn zero() -> Self;
+
+    fn one() -> Self;
+
+    /// The cell holding `value`, or `None` if it is out of range
+    fn from_i64(value: i64) -> Option<Self>;
+
+    /// The value as an `i64`, or `None` if it is out of range
+    fn to_i64(&self) -> Option<i64>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+    fn is_zero(&self) -> bool;
+}
+
+impl Cell for i64 {
+    fn zero() -> i64 {
+        0
+    }
+
+    fn one() -> i64 {
+        1
+    }
+
+    fn from_i64(value: i64) -> Option<i64> {
+        Some(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self)
+    }
+
+    fn checked_add(&self, other: &i64) -> Option<i64> {
+        i64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &i64) -> Option<i64> {
+        i64::checked_mul(*self, *other)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl Cell for i32 {
+    fn zero() -> i32 {
+        0
+    }
+
+    fn one() -> i32 {
+        1
+    }
+
+    fn from_i64(value: i64) -> Option<i32> {
+        i32::try_from(value).ok()
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        Some(*self as i64)
+    }
+
+    fn checked_add(&self, other: &i32) -> Option<i32> {
+        i32::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &i32) -> Option<i32> {
+        i32::checked_mul(*self, *other)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+}
+
+impl Cell for BigInt {
+    fn zero() -> BigInt {
+        num::Zero::zero()
+    }
+
+    fn one() -> BigInt {
+        num::One::one()
+    }
+
+    fn from_i64(value: i64) -> Option<BigInt> {
+        Some(BigInt::from(value))
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn checked_add(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self * other)
+    }
+
+    fn is_zero(&self) -> bool {
+        num::Zero::is_zero(self)
+    }
+}
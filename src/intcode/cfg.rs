@@ -0,0 +1,336 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use super::{Disassembly, Instruction, Opcode, ParamMode};
+
+/// How control passes along an edge of a `ControlFlowGraph`
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum EdgeKind {
+    /// Execution runs on into the next instruction
+    Fallthrough,
+    /// A jump instruction with an immediate target was taken
+    Jump,
+}
+
+/// An edge between two blocks, identified by their start addresses
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only ever entered at the top and left at the bottom
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Block {
+    pub instructions: Vec<Instruction>,
+    /// Whether the block ends in a jump whose target is read from memory, so that where it goes
+    /// cannot be known without running the program
+    pub dynamic_exit: bool,
+}
+
+impl Block {
+    pub fn start(&self) -> usize {
+        self.instructions[0].addr
+    }
+
+    /// The address just past the last cell of the block
+    pub fn end(&self) -> usize {
+        let last = self.instructions.last().unwrap();
+        last.addr + last.size()
+    }
+}
+
+/// An instruction that writes to a cell holding part of a reachable instruction
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct CodeWrite {
+    pub pc: usize,
+    pub addr: usize,
+}
+
+/// The basic blocks of an Intcode program and the edges between them, recovered without running
+/// it.
+///
+/// Like `Disassembly`, only jumps to immediate mode targets can be followed, so code reached only
+/// through a dynamic jump is missing unless its address is given as an entry point. Writes are
+/// only checked against the code when their address is known statically, so self-modification
+/// through a relative mode parameter goes unreported.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ControlFlowGraph {
+    /// The blocks, in order of address
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+    pub code_writes: Vec<CodeWrite>,
+}
+
+impl ControlFlowGraph {
+    /// Recover the graph of a program that starts executing at address 0
+    pub fn new(code: &[i64]) -> ControlFlowGraph {
+        ControlFlowGraph::with_entry_points(code, &[0])
+    }
+
+    /// Recover the graph of a program, treating each of `entry_points` as the start of reachable
+    /// code
+    pub fn with_entry_points(code: &[i64], entry_points: &[usize]) -> ControlFlowGraph {
+        let instructions = Disassembly::find_reachable(code, entry_points)
+            .into_iter()
+            .filter_map(|addr| Instruction::decode(code, addr))
+            .collect::<Vec<_>>();
+
+        let mut leaders = entry_points.iter().cloned().collect::<BTreeSet<usize>>();
+        for inst in &instructions {
+            if ControlFlowGraph::ends_block(inst) {
+                leaders.extend(inst.successors());
+            }
+        }
+
+        let mut blocks: Vec<Block> = Vec::new();
+        for inst in instructions.iter().cloned() {
+            match blocks.last_mut() {
+                Some(block)
+                    if block.end() == inst.addr
+                        && !leaders.contains(&inst.addr)
+                        && !ControlFlowGraph::ends_block(block.instructions.last().unwrap()) =>
+                {
+                    block.instructions.push(inst)
+                }
+                _ => blocks.push(Block {
+                    instructions: vec![inst],
+                    dynamic_exit: false,
+                }),
+            }
+        }
+
+        let starts = blocks.iter().map(Block::start).collect::<BTreeSet<_>>();
+        let mut edges = Vec::new();
+        for block in &mut blocks {
+            let last = block.instructions.last().unwrap();
+            block.dynamic_exit = ControlFlowGraph::jumps_dynamically(last);
+            let target = ControlFlowGraph::static_target(last);
+            let successors = last.successors().into_iter().collect::<BTreeSet<_>>();
+            for to in successors.into_iter().filter(|to| starts.contains(to)) {
+                edges.push(Edge {
+                    from: block.instructions[0].addr,
+                    to,
+                    kind: if Some(to) == target {
+                        EdgeKind::Jump
+                    } else {
+                        EdgeKind::Fallthrough
+                    },
+                });
+            }
+        }
+
+        let code_writes = instructions
+            .iter()
+            .filter_map(|inst| {
+                let addr = ControlFlowGraph::static_write(inst)?;
+                instructions
+                    .iter()
+                    .any(|other| other.addr <= addr && addr < other.addr + other.size())
+                    .then_some(CodeWrite {
+                        pc: inst.addr,
+                        addr,
+                    })
+            })
+            .collect();
+
+        ControlFlowGraph {
+            blocks,
+            edges,
+            code_writes,
+        }
+    }
+
+    /// The block containing the instruction cell at `addr`
+    pub fn block_at(&self, addr: usize) -> Option<&Block> {
+        self.blocks
+            .iter()
+            .find(|block| block.start() <= addr && addr < block.end())
+    }
+
+    /// Render the graph in Graphviz DOT format. Jumps are labelled, dynamic jumps lead to a
+    /// single dashed node, and writes into code are drawn as red dotted edges from the writing
+    /// block to the block written to.
+    pub fn dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph intcode {{").unwrap();
+        writeln!(dot, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for block in &self.blocks {
+            let label = block
+                .instructions
+                .iter()
+                .map(|inst| format!("{}: {}\\l", inst.addr, inst))
+                .collect::<String>();
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start(), label).unwrap();
+        }
+
+        for edge in &self.edges {
+            match edge.kind {
+                EdgeKind::Fallthrough => writeln!(dot, "    b{} -> b{};", edge.from, edge.to),
+                EdgeKind::Jump => {
+                    writeln!(dot, "    b{} -> b{} [label=\"jump\"];", edge.from, edge.to)
+                }
+            }
+            .unwrap();
+        }
+
+        if self.blocks.iter().any(|block| block.dynamic_exit) {
+            writeln!(
+                dot,
+                "    dynamic [shape=ellipse, style=dashed, label=\"dynamic jump\"];"
+            )
+            .unwrap();
+            for block in self.blocks.iter().filter(|block| block.dynamic_exit) {
+                writeln!(dot, "    b{} -> dynamic [style=dashed];", block.start()).unwrap();
+            }
+        }
+
+        let writes = self
+            .code_writes
+            .iter()
+            .filter_map(|write| {
+                let from = self.block_at(write.pc)?.start();
+                let to = self.block_at(write.addr)?.start();
+                Some((from, to, write.addr))
+            })
+            .collect::<BTreeSet<_>>();
+        for (from, to, addr) in writes {
+            writeln!(
+                dot,
+                "    b{} -> b{} [style=dotted, color=red, label=\"writes {}\"];",
+                from, to, addr
+            )
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    /// Whether nothing after `inst` can be part of the same block
+    fn ends_block(inst: &Instruction) -> bool {
+        matches!(inst.opcode, Opcode::Jnz | Opcode::Jz | Opcode::Halt)
+    }
+
+    /// The target of a jump given in immediate mode
+    fn static_target(inst: &Instruction) -> Option<usize> {
+        match inst.opcode {
+            Opcode::Jnz | Opcode::Jz
+                if inst.params[1].mode == ParamMode::Immediate && inst.params[1].value >= 0 =>
+            {
+                Some(inst.params[1].value as usize)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `inst` is a jump that may be taken to a target read from memory
+    fn jumps_dynamically(inst: &Instruction) -> bool {
+        match inst.opcode {
+            Opcode::Jnz | Opcode::Jz => {
+                let (cond, target) = (inst.params[0], inst.params[1]);
+                let never_jumps = cond.mode == ParamMode::Immediate
+                    && (cond.value != 0) != (inst.opcode == Opcode::Jnz);
+                target.mode != ParamMode::Immediate && !never_jumps
+            }
+            _ => false,
+        }
+    }
+
+    /// The address `inst` writes to, if that is known without running the program
+    fn static_write(inst: &Instruction) -> Option<usize> {
+        let param = inst.opcode.write_param()?;
+        let operand = inst.params[param];
+        match operand.mode {
+            ParamMode::Position if operand.value >= 0 => Some(operand.value as usize),
+            ParamMode::Immediate => Some(inst.addr + param + 1),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn starts(cfg: &ControlFlowGraph) -> Vec<usize> {
+        cfg.blocks.iter().map(Block::start).collect()
+    }
+
+    #[test]
+    fn test_conditional_jump() {
+        let cfg = ControlFlowGraph::new(&[3, 10, 1005, 10, 7, 104, 0, 104, 1, 99, 0]);
+        assert_eq!(starts(&cfg), vec![0, 5, 7]);
+        assert_eq!(
+            cfg.edges,
+            vec![
+                Edge {
+                    from: 0,
+                    to: 5,
+                    kind: EdgeKind::Fallthrough
+                },
+                Edge {
+                    from: 0,
+                    to: 7,
+                    kind: EdgeKind::Jump
+                },
+                Edge {
+                    from: 5,
+                    to: 7,
+                    kind: EdgeKind::Fallthrough
+                },
+            ]
+        );
+        assert_eq!(
+            cfg.dot(),
+            "digraph intcode {\n\
+             \x20   node [shape=box, fontname=\"monospace\"];\n\
+             \x20   b0 [label=\"0: read [10]\\l2: jnz [10], #7\\l\"];\n\
+             \x20   b5 [label=\"5: output #0\\l\"];\n\
+             \x20   b7 [label=\"7: output #1\\l9: halt\\l\"];\n\
+             \x20   b0 -> b5;\n\
+             \x20   b0 -> b7 [label=\"jump\"];\n\
+             \x20   b5 -> b7;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_dynamic_jump() {
+        let cfg = ControlFlowGraph::new(&[1106, 0, 7, 4, 3, 4, 99, 2105, 1, 0, 99]);
+        assert_eq!(starts(&cfg), vec![0, 7]);
+        assert!(cfg.blocks[1].dynamic_exit);
+        assert!(cfg.dot().contains("    b7 -> dynamic [style=dashed];\n"));
+
+        let cfg = ControlFlowGraph::with_entry_points(
+            &[1106, 0, 7, 4, 3, 4, 99, 2105, 1, 0, 99],
+            &[0, 3],
+        );
+        assert_eq!(starts(&cfg), vec![0, 3, 7]);
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        // Turns the second add into a halt before reaching it
+        let cfg = ControlFlowGraph::new(&[1101, 0, 99, 4, 1, 9, 9, 9, 99, 0]);
+        assert_eq!(starts(&cfg), vec![0]);
+        assert_eq!(cfg.code_writes, vec![CodeWrite { pc: 0, addr: 4 }]);
+        assert!(cfg
+            .dot()
+            .contains("    b0 -> b0 [style=dotted, color=red, label=\"writes 4\"];\n"));
+    }
+
+    #[test]
+    fn test_loop() {
+        // Counts down from 5 and halts
+        let cfg = ControlFlowGraph::new(&[1001, 10, -1, 10, 1005, 10, 0, 99, 0, 0, 5]);
+        assert_eq!(starts(&cfg), vec![0, 7]);
+        assert_eq!(
+            cfg.edges
+                .iter()
+                .map(|edge| (edge.from, edge.to))
+                .collect::<Vec<_>>(),
+            vec![(0, 0), (0, 7)]
+        );
+    }
+}
@@ -0,0 +1,537 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Write;
+
+use super::{
+    Disassembly, Instruction, IntcodeError, Observer, Opcode, ParamMode, Processor, ProcessorState,
+    PAGE_SIZE,
+};
+
+/// How many pages past the program image to also keep in a runtime's flat cells, which is
+/// usually enough to hold a program's stack and working data
+const FLAT_PAGES_BEYOND_CODE: usize = 3;
+
+/// How many values of the program image to put on each line of the generated source
+const VALUES_PER_LINE: usize = 16;
+
+/// Translate an Intcode program into the source of a Rust module, so that it can be compiled
+/// rather than interpreted.
+///
+/// The module defines a `Program` whose `execute` behaves exactly like `Processor::execute` on
+/// the same code. Every instruction that looks reachable without running the program becomes an
+/// arm of a `match` on the program counter. Anything else, such as code reached only through a
+/// computed jump or an instruction that has been overwritten since the program started, is run
+/// by the interpreter a step at a time. `intcode` is the path through which the generated code can
+/// reach this module, such as `advent_2019::intcode`.
+pub fn compile(code: &[i64], intcode: &str) -> String {
+    let mut source = String::new();
+    writeln!(
+        source,
+        "//! Compiled from an Intcode program by `intcode::compile`. Do not edit.\n\n\
+         use std::collections::VecDeque;\n\n\
+         use {}::{{IntcodeError, Processor, ProcessorState, Runtime}};\n",
+        intcode
+    )
+    .unwrap();
+
+    writeln!(source, "const CODE: [i64; {}] = [", code.len()).unwrap();
+    for values in code.chunks(VALUES_PER_LINE) {
+        let values = values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        writeln!(source, "    {},", values.join(", ")).unwrap();
+    }
+    writeln!(source, "];\n").unwrap();
+
+    source.push_str(
+        "/// The compiled program, which runs like a `Processor` loaded with the same code
+#[derive(Debug, Clone)]
+pub struct Program {
+    runtime: Runtime,
+}
+
+impl Default for Program {
+    fn default() -> Program {
+        Program::new()
+    }
+}
+
+impl Program {
+    pub fn new() -> Program {
+        Program {
+            runtime: Runtime::new(&CODE),
+        }
+    }
+
+    /// The processor holding the program's memory and registers
+    pub fn processor(&self) -> &Processor {
+        self.runtime.processor()
+    }
+
+    /// Run the program until it halts or blocks waiting on input, as `Processor::execute` does
+    pub fn execute(
+        &mut self,
+        input: VecDeque<i64>,
+    ) -> Result<(ProcessorState, VecDeque<i64>), IntcodeError> {
+        let result = self.run(input);
+        self.runtime.sync()?;
+        result
+    }
+
+    fn run(
+        &mut self,
+        mut input: VecDeque<i64>,
+    ) -> Result<(ProcessorState, VecDeque<i64>), IntcodeError> {
+        let rt = &mut self.runtime;
+        let mut output = VecDeque::new();
+        loop {
+            match rt.compiled_pc() {
+",
+    );
+
+    for inst in compilable(code) {
+        writeln!(source, "                // {}", inst).unwrap();
+        writeln!(source, "                Some({}) => {{", inst.addr).unwrap();
+        for line in translate(&inst) {
+            writeln!(source, "                    {}", line).unwrap();
+        }
+        writeln!(source, "                }}").unwrap();
+    }
+
+    source.push_str(
+        "                _ => {
+                    if let Some(state) = rt.interpret(&mut input, &mut output)? {
+                        return Ok((state, output));
+                    }
+                }
+            }
+        }
+    }
+}
+",
+    );
+    source
+}
+
+/// The instructions of `code` worth translating. As well as everything reachable from the start,
+/// this includes code reachable from any immediate value that could be an address, since that is
+/// how programs pass return addresses to the dynamic jumps they return with. Position mode
+/// parameters with negative addresses are left to the interpreter, which knows how to fail on
+/// them.
+fn compilable(code: &[i64]) -> Vec<Instruction> {
+    let mut entry_points = BTreeSet::new();
+    entry_points.insert(0);
+    loop {
+        let entry = entry_points.iter().cloned().collect::<Vec<_>>();
+        let instructions = Disassembly::find_reachable(code, &entry)
+            .into_iter()
+            .filter_map(|addr| Instruction::decode(code, addr))
+            .collect::<Vec<_>>();
+        let before = entry_points.len();
+        entry_points.extend(
+            instructions
+                .iter()
+                .flat_map(|inst| inst.params.iter())
+                .filter(|param| {
+                    param.mode == ParamMode::Immediate
+                        && param.value >= 0
+                        && (param.value as usize) < code.len()
+                })
+                .map(|param| param.value as usize),
+        );
+        if entry_points.len() == before {
+            return instructions
+                .into_iter()
+                .filter(|inst| {
+                    inst.params
+                        .iter()
+                        .all(|param| param.mode != ParamMode::Position || param.value >= 0)
+                })
+                .collect();
+        }
+    }
+}
+
+/// The statements that carry out `inst`, in the same order as the interpreter so that any
+/// error is the same one it would report
+fn translate(inst: &Instruction) -> Vec<String> {
+    let mut lines = Vec::new();
+    let next = inst.addr + inst.size();
+    for (i, param) in inst.params.iter().enumerate() {
+        if param.mode == ParamMode::Relative {
+            lines.push(format!("let a{} = rt.relative({})?;", i + 1, param.value));
+        }
+    }
+    let immediate = |i: usize| inst.params[i].mode == ParamMode::Immediate;
+    let arg = |i: usize| {
+        let param = inst.params[i];
+        match param.mode {
+            ParamMode::Immediate => param.value.to_string(),
+            ParamMode::Position => format!("rt.load({})", param.value),
+            ParamMode::Relative => format!("rt.load(a{})", i + 1),
+        }
+    };
+    let dest = |i: usize| {
+        let param = inst.params[i];
+        match param.mode {
+            ParamMode::Immediate => (inst.addr + i + 1).to_string(),
+            ParamMode::Position => param.value.to_string(),
+            ParamMode::Relative => format!("a{}", i + 1),
+        }
+    };
+
+    match inst.opcode {
+        Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+            let (a, b) = (arg(0), arg(1));
+            let value = match inst.opcode {
+                Opcode::Add => format!("rt.add({}, {})?", a, b),
+                Opcode::Mul => format!("rt.mul({}, {})?", a, b),
+                // Comparing a value with itself, or two constants, always has the same result
+                Opcode::Lt if a == b => "0".to_string(),
+                Opcode::Eq if a == b => "1".to_string(),
+                Opcode::Lt | Opcode::Eq if immediate(0) && immediate(1) => {
+                    let (a, b) = (inst.params[0].value, inst.params[1].value);
+                    let result = if inst.opcode == Opcode::Lt {
+                        a < b
+                    } else {
+                        a == b
+                    };
+                    (result as i64).to_string()
+                }
+                Opcode::Lt => format!("i64::from({} < {})", a, b),
+                _ => format!("i64::from({} == {})", a, b),
+            };
+            lines.push(format!("let value = {};", value));
+            lines.push(format!("rt.store({}, value)?;", dest(2)));
+        }
+        Opcode::Read => {
+            lines.push(format!("rt.check_writable({})?;", dest(0)));
+            lines.push("let value = match input.pop_front() {".to_string());
+            lines.push("    Some(value) => value,".to_string());
+            lines.push("    None => return Ok((ProcessorState::IoWait, output)),".to_string());
+            lines.push("};".to_string());
+            lines.push(format!("rt.store({}, value)?;", dest(0)));
+        }
+        Opcode::Output => lines.push(format!("output.push_back({});", arg(0))),
+        Opcode::Jnz | Opcode::Jz if immediate(0) => {
+            if (inst.params[0].value != 0) == (inst.opcode == Opcode::Jnz) {
+                lines.push(format!("let target = rt.target({})?;", arg(1)));
+                lines.push("rt.goto(target);".to_string());
+            } else {
+                lines.push(format!("rt.goto({});", next));
+            }
+            return lines;
+        }
+        Opcode::Jnz | Opcode::Jz => {
+            let comparison = if inst.opcode == Opcode::Jnz {
+                "!="
+            } else {
+                "=="
+            };
+            lines.push(format!("if {} {} 0 {{", arg(0), comparison));
+            lines.push(format!("    let target = rt.target({})?;", arg(1)));
+            lines.push("    rt.goto(target);".to_string());
+            lines.push("} else {".to_string());
+            lines.push(format!("    rt.goto({});", next));
+            lines.push("}".to_string());
+            return lines;
+        }
+        Opcode::Rel => lines.push(format!("rt.adjust_rel({})?;", arg(0))),
+        Opcode::Halt => {
+            lines.push("return Ok((ProcessorState::Halted, output));".to_string());
+            return lines;
+        }
+    }
+    lines.push(format!("rt.goto({});", next));
+    lines
+}
+
+/// The state of a compiled program, along with the operations its generated code is built from.
+///
+/// The state is kept in a `Processor`, which also runs anything that was not compiled. Since
+/// nearly every access is to the program image or a little way beyond it, those cells are also
+/// kept in a flat array that compiled code works on directly, and are only copied back to the
+/// processor when it is needed. Any write to a cell the program started with marks the
+/// instructions that may include it as stale, after which they are left to the interpreter too.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Runtime {
+    processor: Processor,
+    /// The cells at the bottom of memory
+    cells: Vec<i64>,
+    /// The addresses in `cells` written by compiled code since the processor was last brought
+    /// up to date, each listed once
+    dirty: Vec<usize>,
+    /// Whether each address in `cells` is listed in `dirty`
+    is_dirty: Vec<bool>,
+    stale: Vec<bool>,
+}
+
+impl Runtime {
+    pub fn new(code: &[i64]) -> Runtime {
+        let mut cells = code.to_vec();
+        cells.resize(
+            (code.len().div_ceil(PAGE_SIZE) + FLAT_PAGES_BEYOND_CODE) * PAGE_SIZE,
+            0,
+        );
+        Runtime {
+            processor: Processor::new(code.to_vec()),
+            is_dirty: vec![false; cells.len()],
+            cells,
+            dirty: Vec::new(),
+            stale: vec![false; code.len()],
+        }
+    }
+
+    /// The processor holding the program's state, which is only up to date between runs
+    pub fn processor(&self) -> &Processor {
+        &self.processor
+    }
+
+    /// The program counter, or `None` if the instruction there may have been overwritten
+    pub fn compiled_pc(&self) -> Option<usize> {
+        let pc = self.processor.pc();
+        match self.stale.get(pc) {
+            Some(true) => None,
+            _ => Some(pc),
+        }
+    }
+
+    pub fn goto(&mut self, pc: usize) {
+        self.processor.set_pc(pc);
+    }
+
+    pub fn load(&self, addr: usize) -> i64 {
+        match self.cells.get(addr) {
+            Some(value) => *value,
+            None => self.processor.memory().get(addr),
+        }
+    }
+
+    pub fn store(&mut self, addr: usize, value: i64) -> Result<(), IntcodeError> {
+        if addr < self.cells.len() {
+            self.cells[addr] = value;
+            if !self.is_dirty[addr] {
+                self.is_dirty[addr] = true;
+                self.dirty.push(addr);
+            }
+        } else {
+            self.processor.write(addr, value)?;
+        }
+        mark_stale(&mut self.stale, addr);
+        Ok(())
+    }
+
+    /// Check that an input instruction could store any value at `addr`, before it reads one
+    pub fn check_writable(&self, addr: usize) -> Result<(), IntcodeError> {
+        if addr < self.cells.len() {
+            Ok(())
+        } else {
+            self.processor.check_writable(addr)
+        }
+    }
+
+    /// The address of a relative mode parameter
+    pub fn relative(&self, offset: i64) -> Result<usize, IntcodeError> {
+        let addr = self.overflow(self.processor.rel().checked_add(offset))?;
+        self.target(addr)
+    }
+
+    /// Check that `addr` can be used as an address
+    pub fn target(&self, addr: i64) -> Result<usize, IntcodeError> {
+        if addr < 0 {
+            Err(IntcodeError::NegativeAddress {
+                pc: self.processor.pc(),
+                addr,
+            })
+        } else {
+            Ok(addr as usize)
+        }
+    }
+
+    pub fn add(&self, a: i64, b: i64) -> Result<i64, IntcodeError> {
+        self.overflow(a.checked_add(b))
+    }
+
+    pub fn mul(&self, a: i64, b: i64) -> Result<i64, IntcodeError> {
+        self.overflow(a.checked_mul(b))
+    }
+
+    pub fn adjust_rel(&mut self, offset: i64) -> Result<(), IntcodeError> {
+        let rel = self.overflow(self.processor.rel().checked_add(offset))?;
+        self.processor.set_rel(rel);
+        Ok(())
+    }
+
+    /// Run a single instruction in the interpreter
+    pub fn interpret(
+        &mut self,
+        input: &mut VecDeque<i64>,
+        output: &mut VecDeque<i64>,
+    ) -> Result<Option<ProcessorState>, IntcodeError> {
+        self.sync()?;
+        let mut observer = Mirror {
+            cells: &mut self.cells,
+            stale: &mut self.stale,
+        };
+        self.processor.step_observed(input, output, &mut observer)
+    }
+
+    /// Bring the processor up to date with everything compiled code has written
+    pub fn sync(&mut self) -> Result<(), IntcodeError> {
+        while let Some(&addr) = self.dirty.last() {
+            self.processor.write(addr, self.cells[addr])?;
+            self.is_dirty[addr] = false;
+            self.dirty.pop();
+        }
+        Ok(())
+    }
+
+    fn overflow<T>(&self, result: Option<T>) -> Result<T, IntcodeError> {
+        result.ok_or(IntcodeError::Overflow {
+            pc: self.processor.pc(),
+        })
+    }
+}
+
+/// Mark every instruction that could include the cell at `addr` as stale
+fn mark_stale(stale: &mut [bool], addr: usize) {
+    let end = (addr + 1).min(stale.len());
+    let start = addr.saturating_sub(Opcode::MAX_PARAMS).min(end);
+    for cell in &mut stale[start..end] {
+        *cell = true;
+    }
+}
+
+/// Copies the interpreter's writes into the flat cells, marking what they overwrite as stale
+struct Mirror<'a> {
+    cells: &'a mut [i64],
+    stale: &'a mut [bool],
+}
+
+impl<'a> Observer<i64> for Mirror<'a> {
+    fn memory_write(&mut self, addr: usize, _old: &i64, new: &i64) {
+        if let Some(cell) = self.cells.get_mut(addr) {
+            *cell = *new;
+        }
+        mark_stale(self.stale, addr);
+    }
+}
+
+/// The programs in `build.rs`, compiled by `compile` when the crate is built
+#[cfg(test)]
+mod compiled {
+    include!(concat!(env!("OUT_DIR"), "/compiled.rs"));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::parse_program;
+
+    fn day05() -> Vec<i64> {
+        parse_program(include_str!("../bin/day05.txt")).unwrap()
+    }
+
+    fn day09() -> Vec<i64> {
+        parse_program(include_str!("../bin/day09.txt")).unwrap()
+    }
+
+    /// The day05 sample that compares its input with 8, outputting 999, 1000 or 1001
+    fn compare() -> Vec<i64> {
+        parse_program(include_str!("testdata/compare.txt")).unwrap()
+    }
+
+    /// Outputs its input doubled, by turning an add of 2 into a multiply before reaching it
+    fn self_modifying() -> Vec<i64> {
+        parse_program(include_str!("testdata/self_modifying.txt")).unwrap()
+    }
+
+    #[test]
+    fn test_day05() {
+        for input in &[1, 5] {
+            let mut compiled = compiled::day05::Program::new();
+            let mut interpreted = Processor::new(day05());
+            assert_eq!(
+                compiled.execute(deque!(*input)),
+                interpreted.execute(deque!(*input))
+            );
+            assert_eq!(compiled.processor(), &interpreted);
+        }
+    }
+
+    #[test]
+    fn test_day09() {
+        for input in &[1, 2] {
+            let mut compiled = compiled::day09::Program::new();
+            let mut interpreted = Processor::new(day09());
+            assert_eq!(
+                compiled.execute(deque!(*input)),
+                interpreted.execute(deque!(*input))
+            );
+            assert_eq!(compiled.processor(), &interpreted);
+        }
+    }
+
+    #[test]
+    fn test_resume_after_input() {
+        for input in 7..=9 {
+            let mut compiled = compiled::compare::Program::new();
+            let mut interpreted = Processor::new(compare());
+            assert_eq!(
+                compiled.execute(deque!()),
+                Ok((ProcessorState::IoWait, deque!()))
+            );
+            assert_eq!(
+                compiled.execute(deque!(input)),
+                interpreted.execute(deque!(input))
+            );
+            assert_eq!(compiled.processor(), &interpreted);
+        }
+    }
+
+    #[test]
+    fn test_self_modifying() {
+        let mut compiled = compiled::self_modifying::Program::new();
+        assert_eq!(
+            compiled.execute(deque!(21)),
+            Ok((ProcessorState::Halted, deque!(42)))
+        );
+        let mut interpreted = Processor::new(self_modifying());
+        interpreted.execute(deque!(21)).unwrap();
+        assert_eq!(compiled.processor(), &interpreted);
+    }
+
+    #[test]
+    fn test_sync_writes_dirty_cells() {
+        let mut runtime = Runtime::new(&[99]);
+        runtime.store(5, 7).unwrap();
+        runtime.store(3, 0).unwrap();
+        runtime.store(5, 8).unwrap();
+        runtime.sync().unwrap();
+        let mut interpreted = Processor::new(vec![99]);
+        interpreted.write(5, 8).unwrap();
+        interpreted.write(3, 0).unwrap();
+        assert_eq!(runtime.processor(), &interpreted);
+        assert_eq!(runtime.processor().memory().extent(), 6);
+
+        // Cells are only copied back once
+        interpreted.write(5, 1).unwrap();
+        runtime.processor = interpreted.clone();
+        runtime.sync().unwrap();
+        assert_eq!(runtime.processor(), &interpreted);
+    }
+
+    #[test]
+    fn test_errors_match() {
+        let mut runtime = Runtime::new(&[109, -5, 99]);
+        runtime.adjust_rel(-5).unwrap();
+        assert_eq!(
+            runtime.relative(2),
+            Err(IntcodeError::NegativeAddress { pc: 0, addr: -3 })
+        );
+        assert_eq!(
+            runtime.mul(i64::MAX, 2),
+            Err(IntcodeError::Overflow { pc: 0 })
+        );
+    }
+}
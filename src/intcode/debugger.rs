@@ -0,0 +1,601 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+
+use super::{Instruction, IntcodeError, Processor, ProcessorState, Undo};
+
+/// The number of memory cells shown on each line by the examine command
+const CELLS_PER_LINE: usize = 8;
+
+const HELP: &str = "\
+Commands:
+  s, step [n]            execute n instructions (default 1)
+  c, continue            run until a breakpoint, watchpoint, input wait or halt
+  rs, rstep [n]          undo the last n instructions (default 1)
+  rc, rcontinue          run backwards until a breakpoint, watchpoint or the start
+  lw, lastwrite <addr>   run backwards to the instruction that last wrote addr
+  g, goto <count>        run forwards or backwards to after count instructions
+  b, break <addr>        stop before executing the instruction at addr
+  d, delete <addr>       remove the breakpoint at addr
+  w, watch <addr>        stop after the value at addr changes
+  unwatch <addr>         remove the watchpoint at addr
+  i, input <value...>    queue values to be read by the program
+  r, regs                show pc, relative base and instruction count
+  x <addr> [count]       show count memory cells starting at addr (default 8)
+  l, list [addr] [count] disassemble count instructions from addr (default pc, 5)
+  info                   list breakpoints, watchpoints and pending input
+  h, help                show this message
+  q, quit                exit the debugger";
+
+/// Why the debugger handed control back to the user
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StopReason {
+    /// The requested number of instructions were executed
+    Stepped,
+    /// Execution reached an instruction with a breakpoint on it
+    Breakpoint(usize),
+    /// An instruction changed the value of a watched memory cell
+    Watchpoint { addr: usize, old: i64, new: i64 },
+    /// The processor halted or is waiting for input
+    State(ProcessorState),
+    /// The processor failed to execute an instruction
+    Error(IntcodeError),
+    /// Running backwards undid the last write to `addr`, which changed it from `old` to `new`
+    LastWrite { addr: usize, old: i64, new: i64 },
+    /// Running backwards reached the start of the program
+    Start,
+}
+
+/// Drives a `Processor` one instruction at a time, stopping at breakpoints and watchpoints.
+///
+/// Every instruction executed is recorded so that it can be undone, letting a session run
+/// backwards as well as forwards. The record grows with the number of instructions executed.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    proc: Processor,
+    input: VecDeque<i64>,
+    output: VecDeque<i64>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: BTreeSet<usize>,
+    steps: u64,
+    history: Vec<Undo>,
+}
+
+impl Debugger {
+    pub fn new(code: Vec<i64>) -> Debugger {
+        Debugger {
+            proc: Processor::new(code),
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            steps: 0,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn processor(&self) -> &Processor {
+        &self.proc
+    }
+
+    /// The number of instructions executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Queue values for the program to read
+    pub fn feed<I: IntoIterator<Item = i64>>(&mut self, values: I) {
+        self.input.extend(values);
+    }
+
+    /// Remove and return everything the program has output since the last call
+    pub fn take_output(&mut self) -> VecDeque<i64> {
+        std::mem::take(&mut self.output)
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+
+    pub fn add_watchpoint(&mut self, addr: usize) {
+        self.watchpoints.insert(addr);
+    }
+
+    pub fn remove_watchpoint(&mut self, addr: usize) -> bool {
+        self.watchpoints.remove(&addr)
+    }
+
+    /// Read a memory cell, treating memory that has not been allocated yet as 0
+    pub fn read(&self, addr: usize) -> i64 {
+        self.proc.memory().get(addr)
+    }
+
+    /// Execute up to `count` instructions, stopping early on a watchpoint or if the processor
+    /// cannot continue
+    pub fn step(&mut self, count: usize) -> StopReason {
+        for _ in 0..count {
+            if let Some(reason) = self.step_one() {
+                return reason;
+            }
+        }
+        StopReason::Stepped
+    }
+
+    /// Execute instructions until something interesting happens. The instruction at the current
+    /// PC is always executed, so continuing from a breakpoint makes progress.
+    pub fn cont(&mut self) -> StopReason {
+        loop {
+            if let Some(reason) = self.step_one() {
+                return reason;
+            }
+            if self.breakpoints.contains(&self.proc.pc()) {
+                return StopReason::Breakpoint(self.proc.pc());
+            }
+        }
+    }
+
+    fn step_one(&mut self) -> Option<StopReason> {
+        let watched = self
+            .watchpoints
+            .iter()
+            .map(|addr| (*addr, self.read(*addr)))
+            .collect::<BTreeMap<usize, i64>>();
+
+        match self
+            .proc
+            .step_undoable(&mut self.input, &mut self.output, &mut self.history)
+        {
+            Err(err) => return Some(StopReason::Error(err)),
+            Ok(Some(state)) => return Some(StopReason::State(state)),
+            Ok(None) => self.steps += 1,
+        }
+
+        watched
+            .into_iter()
+            .map(|(addr, old)| (addr, old, self.read(addr)))
+            .find(|(_, old, new)| old != new)
+            .map(|(addr, old, new)| StopReason::Watchpoint { addr, old, new })
+    }
+
+    /// Undo up to `count` instructions, stopping early on a watchpoint or at the start
+    pub fn reverse_step(&mut self, count: usize) -> StopReason {
+        for _ in 0..count {
+            if let Some(reason) = self.unstep_one() {
+                return reason;
+            }
+        }
+        StopReason::Stepped
+    }
+
+    /// Undo instructions until reaching a breakpoint, undoing a change to a watched cell, or
+    /// arriving back at the start. The last instruction is always undone, so continuing
+    /// backwards from a breakpoint makes progress.
+    pub fn reverse_cont(&mut self) -> StopReason {
+        loop {
+            if let Some(reason) = self.unstep_one() {
+                return reason;
+            }
+            if self.breakpoints.contains(&self.proc.pc()) {
+                return StopReason::Breakpoint(self.proc.pc());
+            }
+        }
+    }
+
+    /// Undo instructions up to and including the last one that wrote to `addr`, leaving the
+    /// program counter on it, regardless of breakpoints and watchpoints
+    pub fn reverse_to_write(&mut self, addr: usize) -> StopReason {
+        loop {
+            let wrote = match self.history.last() {
+                Some(undo) => undo.writes.iter().any(|(written, _)| *written == addr),
+                None => return StopReason::Start,
+            };
+            let new = self.read(addr);
+            self.unstep_one();
+            if wrote {
+                let old = self.read(addr);
+                return StopReason::LastWrite { addr, old, new };
+            }
+        }
+    }
+
+    /// Run forwards or backwards until exactly `steps` instructions have been executed. Running
+    /// forwards stops early for the same reasons as `step`, and backwards for watchpoints.
+    pub fn goto(&mut self, steps: u64) -> StopReason {
+        if steps < self.steps {
+            self.reverse_step((self.steps - steps) as usize)
+        } else {
+            self.step((steps - self.steps) as usize)
+        }
+    }
+
+    /// Undo the last instruction executed, giving back its input and taking back its output,
+    /// and report if that changed a watched cell
+    fn unstep_one(&mut self) -> Option<StopReason> {
+        let undo = match self.history.pop() {
+            Some(undo) => undo,
+            None => return Some(StopReason::Start),
+        };
+        let watched = undo
+            .writes
+            .iter()
+            .filter(|(addr, _)| self.watchpoints.contains(addr))
+            .map(|(addr, old)| (*addr, *old, self.read(*addr)))
+            .find(|(_, old, new)| old != new);
+
+        self.proc.undo(&undo);
+        self.steps -= 1;
+        if let Some(value) = undo.input {
+            self.input.push_front(value);
+        }
+        // Output that has already been taken stays with whoever took it
+        if undo.output.is_some() {
+            self.output.pop_back();
+        }
+        watched.map(|(addr, old, new)| StopReason::Watchpoint { addr, old, new })
+    }
+
+    /// Run a single line of debugger input, returning the text to show the user or `None` if
+    /// the user asked to quit
+    pub fn command(&mut self, line: &str) -> Option<String> {
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Some(String::new()),
+        };
+        let nums = match args
+            .iter()
+            .map(|arg| arg.parse::<i64>())
+            .collect::<Result<Vec<i64>, _>>()
+        {
+            Ok(nums) => nums,
+            Err(_) => return Some(format!("Invalid number in '{}'", line.trim())),
+        };
+        let addr_arg = |i: usize| nums.get(i).filter(|addr| **addr >= 0).map(|a| *a as usize);
+
+        let result = match (name, nums.len()) {
+            ("q", 0) | ("quit", 0) => return None,
+            ("h", 0) | ("help", 0) => HELP.to_string(),
+            ("s", 0) | ("step", 0) => self.describe_run(|dbg| dbg.step(1)),
+            ("s", 1) | ("step", 1) if nums[0] > 0 => {
+                let count = nums[0] as usize;
+                self.describe_run(|dbg| dbg.step(count))
+            }
+            ("c", 0) | ("continue", 0) => self.describe_run(Debugger::cont),
+            ("rs", 0) | ("rstep", 0) => self.describe_run(|dbg| dbg.reverse_step(1)),
+            ("rs", 1) | ("rstep", 1) if nums[0] > 0 => {
+                let count = nums[0] as usize;
+                self.describe_run(|dbg| dbg.reverse_step(count))
+            }
+            ("rc", 0) | ("rcontinue", 0) => self.describe_run(Debugger::reverse_cont),
+            ("lw", 1) | ("lastwrite", 1) if addr_arg(0).is_some() => {
+                let addr = nums[0] as usize;
+                self.describe_run(|dbg| dbg.reverse_to_write(addr))
+            }
+            ("g", 1) | ("goto", 1) if nums[0] >= 0 => {
+                let steps = nums[0] as u64;
+                self.describe_run(|dbg| dbg.goto(steps))
+            }
+            ("b", 1) | ("break", 1) if addr_arg(0).is_some() => {
+                self.add_breakpoint(nums[0] as usize);
+                format!("Breakpoint set at {}", nums[0])
+            }
+            ("d", 1) | ("delete", 1) if addr_arg(0).is_some() => {
+                if self.remove_breakpoint(nums[0] as usize) {
+                    format!("Breakpoint at {} removed", nums[0])
+                } else {
+                    format!("No breakpoint at {}", nums[0])
+                }
+            }
+            ("w", 1) | ("watch", 1) if addr_arg(0).is_some() => {
+                self.add_watchpoint(nums[0] as usize);
+                format!("Watchpoint set on {}", nums[0])
+            }
+            ("unwatch", 1) if addr_arg(0).is_some() => {
+                if self.remove_watchpoint(nums[0] as usize) {
+                    format!("Watchpoint on {} removed", nums[0])
+                } else {
+                    format!("No watchpoint on {}", nums[0])
+                }
+            }
+            ("i", n) | ("input", n) if n > 0 => {
+                self.feed(nums.iter().copied());
+                format!("Queued input: {:?}", self.input)
+            }
+            ("r", 0) | ("regs", 0) => self.describe_regs(),
+            ("x", 1) | ("x", 2) if addr_arg(0).is_some() => {
+                let count = addr_arg(1).unwrap_or(CELLS_PER_LINE);
+                self.describe_memory(nums[0] as usize, count)
+            }
+            ("l", 0) | ("list", 0) => self.describe_code(self.proc.pc(), 5),
+            ("l", 1) | ("list", 1) | ("l", 2) | ("list", 2) if addr_arg(0).is_some() => {
+                self.describe_code(nums[0] as usize, addr_arg(1).unwrap_or(5))
+            }
+            ("info", 0) => format!(
+                "Breakpoints: {:?}\nWatchpoints: {:?}\nPending input: {:?}",
+                self.breakpoints, self.watchpoints, self.input
+            ),
+            _ => format!("Unrecognized command '{}', try 'help'", line.trim()),
+        };
+        Some(result)
+    }
+
+    fn describe_run<F: FnOnce(&mut Debugger) -> StopReason>(&mut self, run: F) -> String {
+        let reason = run(self);
+        let mut out = String::new();
+        let output = self.take_output();
+        if !output.is_empty() {
+            writeln!(out, "Output: {:?}", output).unwrap();
+        }
+        match reason {
+            StopReason::Stepped => (),
+            StopReason::Breakpoint(addr) => writeln!(out, "Breakpoint at {}", addr).unwrap(),
+            StopReason::Watchpoint { addr, old, new } => {
+                writeln!(out, "Watchpoint on {}: {} -> {}", addr, old, new).unwrap()
+            }
+            StopReason::State(ProcessorState::IoWait) => {
+                writeln!(out, "Waiting for input, queue some with 'input'").unwrap()
+            }
+            StopReason::State(ProcessorState::Halted) => writeln!(out, "Halted").unwrap(),
+            StopReason::State(ProcessorState::BudgetExhausted) => {
+                writeln!(out, "Out of budget").unwrap()
+            }
+            StopReason::Error(err) => writeln!(out, "Error: {}", err).unwrap(),
+            StopReason::LastWrite { addr, old, new } => {
+                writeln!(out, "Last write to {}: {} -> {}", addr, old, new).unwrap()
+            }
+            StopReason::Start => writeln!(out, "At the start of the program").unwrap(),
+        }
+        out + &self.describe_code(self.proc.pc(), 1)
+    }
+
+    fn describe_regs(&self) -> String {
+        format!(
+            "pc: {}\nrel: {}\nsteps: {}",
+            self.proc.pc(),
+            self.proc.rel(),
+            self.steps
+        )
+    }
+
+    fn describe_memory(&self, start: usize, count: usize) -> String {
+        (start..start + count)
+            .step_by(CELLS_PER_LINE)
+            .map(|line_start| {
+                let line_end = (line_start + CELLS_PER_LINE).min(start + count);
+                format!(
+                    "{:>6}: {}",
+                    line_start,
+                    (line_start..line_end)
+                        .map(|addr| self.read(addr).to_string())
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn describe_code(&self, start: usize, count: usize) -> String {
+        let mut addr = start;
+        let mut lines: Vec<String> = Vec::new();
+        for _ in 0..count {
+            let marker = if addr == self.proc.pc() { "=>" } else { "  " };
+            let text = match Instruction::decode_with(|addr| Some(self.read(addr)), addr) {
+                Some(inst) => inst.to_string(),
+                None => format!("db {}", self.read(addr)),
+            };
+            lines.push(format!("{} {:>6}: {}", marker, addr, text));
+            addr += Instruction::decode_with(|addr| Some(self.read(addr)), addr)
+                .map_or(1, |inst| inst.size());
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::assemble;
+
+    fn countdown() -> Vec<i64> {
+        assemble(
+            "
+                    read [counter]
+            loop:   output [counter]
+                    add [counter], #-1, [counter]
+                    jnz [counter], #loop
+                    halt
+            counter: db 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_step_and_io_wait() {
+        let mut dbg = Debugger::new(countdown());
+        assert_eq!(dbg.step(1), StopReason::State(ProcessorState::IoWait));
+        assert_eq!(dbg.processor().pc(), 0);
+        dbg.feed(vec![2]);
+        assert_eq!(dbg.step(2), StopReason::Stepped);
+        assert_eq!(dbg.processor().pc(), 4);
+        assert_eq!(dbg.take_output(), deque!(2));
+        assert_eq!(dbg.steps(), 2);
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut dbg = Debugger::new(countdown());
+        dbg.feed(vec![3]);
+        dbg.add_breakpoint(2);
+        assert_eq!(dbg.cont(), StopReason::Breakpoint(2));
+        assert_eq!(dbg.cont(), StopReason::Breakpoint(2));
+        assert_eq!(dbg.take_output(), deque!(3));
+        assert!(dbg.remove_breakpoint(2));
+        assert_eq!(dbg.cont(), StopReason::State(ProcessorState::Halted));
+        assert_eq!(dbg.take_output(), deque!(2, 1));
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut dbg = Debugger::new(countdown());
+        dbg.feed(vec![2]);
+        dbg.add_watchpoint(12);
+        assert_eq!(
+            dbg.cont(),
+            StopReason::Watchpoint {
+                addr: 12,
+                old: 0,
+                new: 2
+            }
+        );
+        assert_eq!(
+            dbg.cont(),
+            StopReason::Watchpoint {
+                addr: 12,
+                old: 2,
+                new: 1
+            }
+        );
+        assert_eq!(dbg.processor().pc(), 8);
+    }
+
+    #[test]
+    fn test_error() {
+        let mut dbg = Debugger::new(vec![42]);
+        assert_eq!(
+            dbg.cont(),
+            StopReason::Error(IntcodeError::UnknownOpcode { pc: 0, opcode: 42 })
+        );
+    }
+
+    #[test]
+    fn test_reverse_step() {
+        let mut dbg = Debugger::new(countdown());
+        dbg.feed(vec![3]);
+        assert_eq!(dbg.cont(), StopReason::State(ProcessorState::Halted));
+        assert_eq!(dbg.take_output(), deque!(3, 2, 1));
+        assert_eq!(dbg.steps(), 10);
+
+        assert_eq!(dbg.reverse_step(3), StopReason::Stepped);
+        assert_eq!((dbg.processor().pc(), dbg.steps(), dbg.read(12)), (2, 7, 1));
+        assert_eq!(dbg.cont(), StopReason::State(ProcessorState::Halted));
+        assert_eq!(dbg.take_output(), deque!(1));
+
+        // Going back to the start gives back the input, so the run can be repeated
+        assert_eq!(dbg.goto(0), StopReason::Stepped);
+        assert_eq!((dbg.processor().pc(), dbg.read(12)), (0, 0));
+        assert_eq!(dbg.reverse_step(1), StopReason::Start);
+        assert_eq!(dbg.goto(4), StopReason::Stepped);
+        assert_eq!(dbg.take_output(), deque!(3));
+        assert_eq!(dbg.cont(), StopReason::State(ProcessorState::Halted));
+        assert_eq!(dbg.take_output(), deque!(2, 1));
+    }
+
+    #[test]
+    fn test_reverse_cont() {
+        let mut dbg = Debugger::new(countdown());
+        dbg.feed(vec![3]);
+        dbg.cont();
+        dbg.add_breakpoint(4);
+        assert_eq!(dbg.reverse_cont(), StopReason::Breakpoint(4));
+        assert_eq!(dbg.read(12), 1);
+        assert!(dbg.remove_breakpoint(4));
+
+        dbg.add_watchpoint(12);
+        assert_eq!(
+            dbg.reverse_cont(),
+            StopReason::Watchpoint {
+                addr: 12,
+                old: 2,
+                new: 1
+            }
+        );
+        assert_eq!(dbg.processor().pc(), 4);
+    }
+
+    #[test]
+    fn test_reverse_to_write() {
+        let mut dbg = Debugger::new(countdown());
+        dbg.feed(vec![2]);
+        dbg.cont();
+        assert_eq!(
+            dbg.reverse_to_write(12),
+            StopReason::LastWrite {
+                addr: 12,
+                old: 1,
+                new: 0
+            }
+        );
+        assert_eq!(dbg.processor().pc(), 4);
+        assert_eq!(
+            dbg.reverse_to_write(12),
+            StopReason::LastWrite {
+                addr: 12,
+                old: 2,
+                new: 1
+            }
+        );
+        assert_eq!(
+            dbg.reverse_to_write(12),
+            StopReason::LastWrite {
+                addr: 12,
+                old: 0,
+                new: 2
+            }
+        );
+        assert_eq!(dbg.processor().pc(), 0);
+        assert_eq!(dbg.reverse_to_write(12), StopReason::Start);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut dbg = Debugger::new(countdown());
+        assert_eq!(
+            dbg.command("list 0 2"),
+            Some("=>      0: read [12]\n        2: output [12]".to_string())
+        );
+        assert_eq!(dbg.command("b 8"), Some("Breakpoint set at 8".to_string()));
+        assert_eq!(
+            dbg.command("c"),
+            Some("Waiting for input, queue some with 'input'\n=>      0: read [12]".to_string())
+        );
+        assert_eq!(
+            dbg.command("input 5"),
+            Some("Queued input: [5]".to_string())
+        );
+        assert_eq!(
+            dbg.command("continue"),
+            Some("Output: [5]\nBreakpoint at 8\n=>      8: jnz [12], #2".to_string())
+        );
+        assert_eq!(
+            dbg.command("regs"),
+            Some("pc: 8\nrel: 0\nsteps: 3".to_string())
+        );
+        assert_eq!(dbg.command("x 10 4"), Some("    10: 2 99 4 0".to_string()));
+        assert_eq!(
+            dbg.command("step"),
+            Some("=>      2: output [12]".to_string())
+        );
+        assert_eq!(
+            dbg.command("rstep"),
+            Some("=>      8: jnz [12], #2".to_string())
+        );
+        assert_eq!(
+            dbg.command("lw 12"),
+            Some("Last write to 12: 5 -> 4\n=>      4: add [12], #-1, [12]".to_string())
+        );
+        assert_eq!(
+            dbg.command("goto 0"),
+            Some("=>      0: read [12]".to_string())
+        );
+        assert_eq!(
+            dbg.command("bogus"),
+            Some("Unrecognized command 'bogus', try 'help'".to_string())
+        );
+        assert_eq!(dbg.command("quit"), None);
+    }
+}
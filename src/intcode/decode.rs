@@ -0,0 +1,106 @@
+use std::fmt::{Debug, Formatter};
+
+use super::{Cell, Opcode, ParamMode};
+
+/// The largest address whose decoded instruction will be cached. Code almost always runs from
+/// the program image, so this only stops a stray jump far into memory from allocating a huge
+/// cache.
+const CACHE_LIMIT: usize = 1 << 16;
+
+/// An instruction parameter, decoded as far as it can be without knowing the relative base
+#[derive(Debug, Clone)]
+pub(crate) enum Param<C> {
+    /// A slot beyond the number of parameters the opcode takes
+    Unused,
+    /// An immediate mode parameter, with the value it holds
+    Immediate(C),
+    /// A position mode parameter, with the address it refers to
+    Position(usize),
+    /// A relative mode parameter, with its offset from the relative base
+    Relative(i64),
+    /// A parameter that cannot be used as an address, because it is negative or does not fit in
+    /// an `i64`. It is kept so that the right error is reported if the instruction runs.
+    Invalid(ParamMode, C),
+}
+
+impl<C: Cell> Param<C> {
+    pub(crate) fn decode(mode: ParamMode, value: C) -> Param<C> {
+        match (mode, value.to_i64()) {
+            (ParamMode::Immediate, _) => Param::Immediate(value),
+            (ParamMode::Position, Some(addr)) if addr >= 0 => Param::Position(addr as usize),
+            (ParamMode::Relative, Some(offset)) => Param::Relative(offset),
+            (mode, _) => Param::Invalid(mode, value),
+        }
+    }
+}
+
+/// An instruction with its opcode and parameters decoded from memory
+#[derive(Debug, Clone)]
+pub(crate) struct Decoded<C> {
+    pub(crate) opcode: Opcode,
+    pub(crate) params: [Param<C>; 3],
+}
+
+/// Instructions decoded at each address, so that a loop does not decode the same instruction
+/// on every pass. An entry is dropped whenever any cell it was decoded from is written.
+///
+/// The cache is derived entirely from memory, so it is ignored when comparing processors.
+#[derive(Clone)]
+pub(crate) struct DecodeCache<C> {
+    entries: Vec<Option<Decoded<C>>>,
+    limit: usize,
+    enabled: bool,
+}
+
+impl<C: Clone> DecodeCache<C> {
+    /// A cache for instructions within the first `extent` cells of memory
+    pub(crate) fn new(extent: usize) -> DecodeCache<C> {
+        DecodeCache {
+            entries: Vec::new(),
+            limit: extent.min(CACHE_LIMIT),
+            enabled: true,
+        }
+    }
+
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.entries.clear();
+    }
+
+    pub(crate) fn get(&self, addr: usize) -> Option<&Decoded<C>> {
+        self.entries.get(addr).and_then(Option::as_ref)
+    }
+
+    pub(crate) fn insert(&mut self, addr: usize, decoded: &Decoded<C>) {
+        if !self.enabled || addr >= self.limit {
+            return;
+        }
+        if addr >= self.entries.len() {
+            self.entries.resize_with(addr + 1, || None);
+        }
+        self.entries[addr] = Some(decoded.clone());
+    }
+
+    /// Forget every instruction that includes the cell at `addr`
+    pub(crate) fn invalidate(&mut self, addr: usize) {
+        let end = (addr + 1).min(self.entries.len());
+        let start = addr.saturating_sub(Opcode::MAX_PARAMS).min(end);
+        for entry in &mut self.entries[start..end] {
+            *entry = None;
+        }
+    }
+}
+
+impl<C> PartialEq for DecodeCache<C> {
+    fn eq(&self, _other: &DecodeCache<C>) -> bool {
+        true
+    }
+}
+
+impl<C> Eq for DecodeCache<C> {}
+
+impl<C> Debug for DecodeCache<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DecodeCache")
+    }
+}
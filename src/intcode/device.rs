@@ -0,0 +1,175 @@
+use std::cell::RefCell;
+
+use super::{
+    AsciiOutput, InputFn, IntcodeError, NoObserver, Observer, Output, OutputFn, Processor,
+    ProcessorState,
+};
+
+/// A peripheral driven by an Intcode machine, such as a screen, a joystick or a robot.
+///
+/// The machine's output is grouped into frames of `frame_size` values, such as the `x, y, tile`
+/// triples drawn on a screen, and each complete frame is handed to `output`. Whenever the
+/// program reads, `input` is asked for a value, after every frame output before the read has
+/// been delivered.
+pub trait Device {
+    /// How many output values make up a single command to the device
+    fn frame_size(&self) -> usize {
+        1
+    }
+
+    /// The next value for the program to read, or `None` if the device has nothing to give
+    /// right now, in which case the machine suspends with `ProcessorState::IoWait`
+    fn input(&mut self) -> Option<i64> {
+        None
+    }
+
+    /// Act on a complete frame of output
+    fn output(&mut self, _frame: &[i64]) {}
+}
+
+/// A printer, which collects text a character at a time
+impl Device for AsciiOutput {
+    fn output(&mut self, frame: &[i64]) {
+        self.write(frame[0]);
+    }
+}
+
+/// An Intcode machine with a device attached, which pumps values between the two until the
+/// program halts or the device runs out of input
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Machine<D> {
+    pub processor: Processor,
+    pub device: D,
+    /// Output values that do not yet make up a whole frame
+    partial: Vec<i64>,
+}
+
+impl<D: Device> Machine<D> {
+    pub fn new(code: Vec<i64>, device: D) -> Machine<D> {
+        Machine::with_processor(Processor::new(code), device)
+    }
+
+    pub fn with_processor(processor: Processor, device: D) -> Machine<D> {
+        Machine {
+            processor,
+            device,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Run until the program halts or waits for input the device cannot give. A frame that is
+    /// only partly output by then is kept until the rest of it arrives on a later run.
+    pub fn run(&mut self) -> Result<ProcessorState, IntcodeError> {
+        self.run_observed(&mut NoObserver)
+    }
+
+    /// Run as `run` does, reporting everything the program does to `observer`
+    pub fn run_observed<O: Observer<i64>>(
+        &mut self,
+        observer: &mut O,
+    ) -> Result<ProcessorState, IntcodeError> {
+        let device = RefCell::new(&mut self.device);
+        let partial = &mut self.partial;
+        self.processor.run_observed(
+            &mut InputFn(|| device.borrow_mut().input()),
+            &mut OutputFn(|value| {
+                partial.push(value);
+                let mut device = device.borrow_mut();
+                if partial.len() >= device.frame_size() {
+                    device.output(partial);
+                    partial.clear();
+                }
+            }),
+            observer,
+        )
+    }
+
+    pub fn into_device(self) -> D {
+        self.device
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// Remembers the sum of each pair it is sent, and supplies numbers counting up from 1
+    #[derive(Debug, Default)]
+    struct Adder {
+        next: i64,
+        sums: Vec<i64>,
+    }
+
+    impl Device for Adder {
+        fn frame_size(&self) -> usize {
+            2
+        }
+
+        fn input(&mut self) -> Option<i64> {
+            if self.next < 3 {
+                self.next += 1;
+                Some(self.next)
+            } else {
+                None
+            }
+        }
+
+        fn output(&mut self, frame: &[i64]) {
+            self.sums.push(frame[0] + frame[1]);
+        }
+    }
+
+    /// Reads a number and outputs it twice, forever
+    fn repeater() -> Vec<i64> {
+        vec![3, 9, 4, 9, 4, 9, 1105, 1, 0, 0]
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut machine = Machine::new(repeater(), Adder::default());
+        assert_eq!(machine.run(), Ok(ProcessorState::IoWait));
+        assert_eq!(machine.into_device().sums, vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn test_partial_frame_kept() {
+        /// Sums triples, so the repeater's pairs straddle frames
+        #[derive(Default)]
+        struct Triples {
+            input: VecDeque<i64>,
+            sums: Vec<i64>,
+        }
+
+        impl Device for Triples {
+            fn frame_size(&self) -> usize {
+                3
+            }
+
+            fn input(&mut self) -> Option<i64> {
+                self.input.pop_front()
+            }
+
+            fn output(&mut self, frame: &[i64]) {
+                self.sums.push(frame.iter().sum());
+            }
+        }
+
+        let mut machine = Machine::new(repeater(), Triples::default());
+        machine.device.input.extend(&[1, 2]);
+        assert_eq!(machine.run(), Ok(ProcessorState::IoWait));
+        assert_eq!(machine.device.sums, vec![4]);
+
+        machine.device.input.push_back(3);
+        assert_eq!(machine.run(), Ok(ProcessorState::IoWait));
+        assert_eq!(machine.device.sums, vec![4, 8]);
+    }
+
+    #[test]
+    fn test_printer() {
+        let code = vec![104, 72, 104, 105, 99];
+        let mut machine = Machine::new(code, AsciiOutput::new());
+        assert_eq!(machine.run(), Ok(ProcessorState::Halted));
+        assert_eq!(machine.device.text(), "Hi");
+    }
+}
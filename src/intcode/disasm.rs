@@ -0,0 +1,332 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Error, Formatter};
+
+use super::{Opcode, ParamMode, Processor};
+
+/// The maximum number of values rendered on a single line of a data region
+const DATA_PER_LINE: usize = 8;
+
+/// A single instruction parameter, along with the addressing mode used to interpret it
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Operand {
+    pub mode: ParamMode,
+    pub value: i64,
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self.mode {
+            ParamMode::Position => write!(f, "[{}]", self.value),
+            ParamMode::Immediate => write!(f, "#{}", self.value),
+            ParamMode::Relative if self.value < 0 => write!(f, "rb{}", self.value),
+            ParamMode::Relative => write!(f, "rb+{}", self.value),
+        }
+    }
+}
+
+/// A fully decoded instruction located at a particular address in memory
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Instruction {
+    pub addr: usize,
+    pub opcode: Opcode,
+    pub params: Vec<Operand>,
+}
+
+impl Instruction {
+    /// Decode the instruction starting at `addr`, or `None` if the value there is not a valid
+    /// instruction or its parameters run off the end of memory
+    pub fn decode(code: &[i64], addr: usize) -> Option<Instruction> {
+        Instruction::decode_with(|addr| code.get(addr).copied(), addr)
+    }
+
+    /// Decode the instruction starting at `addr`, reading memory through `fetch`, which returns
+    /// `None` for addresses beyond the end of memory.
+    ///
+    /// Opcodes with mode digits beyond their last parameter are rejected too. They run the same
+    /// as without those digits, but assembling the instruction would not give back the same
+    /// value, which matters to any program that reads its own code.
+    pub fn decode_with<F: Fn(usize) -> Option<i64>>(fetch: F, addr: usize) -> Option<Instruction> {
+        let raw_opcode = fetch(addr)?;
+        let (opcode, (param1_mode, param2_mode, param3_mode)) =
+            Processor::parse_opcode(addr, raw_opcode).ok()?;
+        let opcode = Opcode::from_code(opcode)?;
+        if raw_opcode >= 10i64.pow(2 + opcode.num_params() as u32) {
+            return None;
+        }
+        let params = [param1_mode, param2_mode, param3_mode]
+            .iter()
+            .take(opcode.num_params())
+            .enumerate()
+            .map(|(i, mode)| fetch(addr + i + 1).map(|value| Operand { mode: *mode, value }))
+            .collect::<Option<Vec<Operand>>>()?;
+
+        Some(Instruction {
+            addr,
+            opcode,
+            params,
+        })
+    }
+
+    /// The number of memory cells occupied by this instruction and its parameters
+    pub fn size(&self) -> usize {
+        1 + self.params.len()
+    }
+
+    /// The addresses execution may continue at after this instruction, as far as can be
+    /// determined without running the program
+    pub(crate) fn successors(&self) -> Vec<usize> {
+        let next = self.addr + self.size();
+        match self.opcode {
+            Opcode::Halt => vec![],
+            Opcode::Jnz | Opcode::Jz => {
+                let (cond, target) = (self.params[0], self.params[1]);
+                let static_target = if target.mode == ParamMode::Immediate && target.value >= 0 {
+                    Some(target.value as usize)
+                } else {
+                    None
+                };
+                if cond.mode == ParamMode::Immediate {
+                    let jumps = (cond.value != 0) == (self.opcode == Opcode::Jnz);
+                    if jumps {
+                        static_target.into_iter().collect()
+                    } else {
+                        vec![next]
+                    }
+                } else {
+                    static_target.into_iter().chain(Some(next)).collect()
+                }
+            }
+            _ => vec![next],
+        }
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        f.write_str(self.opcode.mnemonic())?;
+        for (i, param) in self.params.iter().enumerate() {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+            param.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// One entry in a disassembly listing
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Line {
+    /// An instruction that may be executed
+    Code(Instruction),
+    /// A run of values that are never reached as instructions
+    Data { addr: usize, values: Vec<i64> },
+}
+
+impl Line {
+    pub fn addr(&self) -> usize {
+        match self {
+            Line::Code(inst) => inst.addr,
+            Line::Data { addr, .. } => *addr,
+        }
+    }
+
+    /// Render this line as assembler source, without its address
+    pub fn source(&self) -> String {
+        match self {
+            Line::Code(inst) => inst.to_string(),
+            Line::Data { values, .. } => format!(
+                "db {}",
+                values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// A listing of an Intcode program, split into code that is statically reachable from the
+/// entry points and data that is not
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Disassembly {
+    pub lines: Vec<Line>,
+}
+
+impl Disassembly {
+    /// Disassemble a program that starts executing at address 0
+    pub fn new(code: &[i64]) -> Disassembly {
+        Disassembly::with_entry_points(code, &[0])
+    }
+
+    /// Disassemble a program, treating each of `entry_points` as the start of reachable code.
+    ///
+    /// Jumps to a position or relative mode target cannot be followed statically, so code only
+    /// reachable that way (such as a return address) will be listed as data unless its address
+    /// is supplied here.
+    pub fn with_entry_points(code: &[i64], entry_points: &[usize]) -> Disassembly {
+        let starts = Disassembly::find_reachable(code, entry_points);
+
+        let mut lines: Vec<Line> = Vec::new();
+        let mut addr = 0;
+        while addr < code.len() {
+            let inst = if starts.contains(&addr) {
+                Instruction::decode(code, addr)
+            } else {
+                None
+            };
+            match inst {
+                Some(inst) => {
+                    addr += inst.size();
+                    lines.push(Line::Code(inst));
+                }
+                None => {
+                    match lines.last_mut() {
+                        Some(Line::Data { values, .. }) if values.len() < DATA_PER_LINE => {
+                            values.push(code[addr])
+                        }
+                        _ => lines.push(Line::Data {
+                            addr,
+                            values: vec![code[addr]],
+                        }),
+                    }
+                    addr += 1;
+                }
+            }
+        }
+
+        Disassembly { lines }
+    }
+
+    /// The address of every instruction reachable from `entry_points` by following static jumps
+    pub(crate) fn find_reachable(code: &[i64], entry_points: &[usize]) -> BTreeSet<usize> {
+        let mut starts: BTreeSet<usize> = BTreeSet::new();
+        let mut pending: Vec<usize> = entry_points.to_vec();
+        while let Some(addr) = pending.pop() {
+            if !starts.insert(addr) {
+                continue;
+            }
+            match Instruction::decode(code, addr) {
+                Some(inst) => pending.extend(inst.successors()),
+                None => {
+                    starts.remove(&addr);
+                }
+            }
+        }
+        starts
+    }
+
+    /// Render the listing as assembler source, without addresses
+    pub fn source(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| line.source() + "\n")
+            .collect::<String>()
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        for line in &self.lines {
+            writeln!(f, "{:>6}: {}", line.addr(), line.source())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_operand_format() {
+        assert_eq!(
+            [
+                Operand {
+                    mode: ParamMode::Position,
+                    value: 12
+                },
+                Operand {
+                    mode: ParamMode::Immediate,
+                    value: 5
+                },
+                Operand {
+                    mode: ParamMode::Relative,
+                    value: 3
+                },
+                Operand {
+                    mode: ParamMode::Relative,
+                    value: -3
+                },
+            ]
+            .iter()
+            .map(|op| op.to_string())
+            .collect::<Vec<String>>(),
+            vec!["[12]", "#5", "rb+3", "rb-3"]
+        )
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            Instruction::decode(&[1002, 4, 3, 4, 33], 0).map(|inst| inst.to_string()),
+            Some("mul [4], #3, [4]".to_string())
+        )
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        assert_eq!(Instruction::decode(&[1002, 4, 3], 0), None)
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(Instruction::decode(&[42], 0), None);
+        assert_eq!(Instruction::decode(&[1104, 5], 0), None);
+        assert_eq!(Instruction::decode(&[100001, 0, 0, 0], 0), None)
+    }
+
+    #[test]
+    fn test_data_after_halt() {
+        assert_eq!(
+            Disassembly::new(&[1002, 4, 3, 4, 99, 7, 8]).to_string(),
+            "     0: mul [4], #3, [4]\n     4: halt\n     5: db 7, 8\n"
+        )
+    }
+
+    #[test]
+    fn test_unconditional_jump_skips_data() {
+        assert_eq!(
+            Disassembly::new(&[1105, 1, 5, 42, 43, 204, -1, 99]).source(),
+            "jnz #1, #5\ndb 42, 43\noutput rb-1\nhalt\n"
+        )
+    }
+
+    #[test]
+    fn test_conditional_jump_follows_both() {
+        assert_eq!(
+            Disassembly::new(&[3, 10, 1005, 10, 7, 104, 0, 104, 1, 99, 0]).source(),
+            "read [10]\njnz [10], #7\noutput #0\noutput #1\nhalt\ndb 0\n"
+        )
+    }
+
+    #[test]
+    fn test_dynamic_jump_entry_points() {
+        let code = vec![1106, 0, 7, 4, 3, 4, 99, 2105, 1, 0, 99];
+        assert_eq!(
+            Disassembly::new(&code).source(),
+            "jz #0, #7\ndb 4, 3, 4, 99\njnz #1, rb+0\ndb 99\n"
+        );
+        assert_eq!(
+            Disassembly::with_entry_points(&code, &[0, 3]).source(),
+            "jz #0, #7\noutput [3]\noutput [99]\njnz #1, rb+0\ndb 99\n"
+        );
+    }
+
+    #[test]
+    fn test_long_data_wraps() {
+        assert_eq!(
+            Disassembly::new(&[99, 1, 2, 3, 4, 5, 6, 7, 8, 9]).source(),
+            "halt\ndb 1, 2, 3, 4, 5, 6, 7, 8\ndb 9\n"
+        )
+    }
+}
@@ -0,0 +1,62 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// The ways in which an Intcode program can fail while being executed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum IntcodeError {
+    /// The instruction at `pc` did not decode to a known opcode
+    UnknownOpcode { pc: usize, opcode: i64 },
+    /// Parameter number `param` of the instruction at `pc` used an unknown addressing mode
+    UnknownParamMode {
+        pc: usize,
+        opcode: i64,
+        param: usize,
+        mode: i64,
+    },
+    /// The instruction at `pc` tried to access or jump to a negative address
+    NegativeAddress { pc: usize, addr: i64 },
+    /// The instruction at `pc` computed a value or address too large for the processor's cells
+    Overflow { pc: usize },
+    /// The instruction at `pc` tried to write to `addr`, which would need more than `max_pages`
+    /// pages of memory
+    OutOfMemory {
+        pc: usize,
+        addr: usize,
+        max_pages: usize,
+    },
+}
+
+impl Display for IntcodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntcodeError::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unexpected opcode {} at {}", opcode, pc)
+            }
+            IntcodeError::UnknownParamMode {
+                pc,
+                opcode,
+                param,
+                mode,
+            } => write!(
+                f,
+                "Got unknown parameter mode for opcode {} at {} for pos {} ({})",
+                opcode, pc, param, mode
+            ),
+            IntcodeError::NegativeAddress { pc, addr } => {
+                write!(f, "Negative address {} accessed at {}", addr, pc)
+            }
+            IntcodeError::Overflow { pc } => write!(f, "Arithmetic overflow at {}", pc),
+            IntcodeError::OutOfMemory {
+                pc,
+                addr,
+                max_pages,
+            } => write!(
+                f,
+                "Out of memory writing to {} at {} (limit is {} pages)",
+                addr, pc, max_pages
+            ),
+        }
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,715 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::convert::TryInto;
+use std::fmt::{Display, Error, Formatter};
+use std::ops::RangeInclusive;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::{
+    Cell, Memory, Opcode, ParamMode, Processor, ProcessorState, DEFAULT_MAX_PAGES, PAGE_SIZE,
+};
+
+/// How many instructions `reference` and `Execution::processor` execute before deciding a
+/// program will never halt
+const REFERENCE_STEP_LIMIT: usize = 1_000_000;
+
+/// What a program did when run, which every implementation should agree on
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Execution {
+    pub state: ProcessorState,
+    pub output: Vec<i64>,
+    pub memory: Memory,
+}
+
+impl Execution {
+    /// Run `code` on a `Processor` with cells of type `C`, with or without its decode cache
+    pub fn processor<C: Cell>(
+        code: &[i64],
+        input: &[i64],
+        cached: bool,
+    ) -> Result<Execution, String> {
+        let mut proc = Processor::<C>::from_code(code).map_err(|err| err.to_string())?;
+        proc.set_decode_cache(cached);
+        proc.set_instruction_budget(Some(REFERENCE_STEP_LIMIT as u64));
+        let input = input
+            .iter()
+            .map(|v| C::from_i64(*v).ok_or_else(|| format!("Input {} does not fit in a cell", v)))
+            .collect::<Result<_, _>>()?;
+        let (state, output) = proc.execute(input).map_err(|err| err.to_string())?;
+        if state == ProcessorState::BudgetExhausted {
+            return Err("Did not halt".to_string());
+        }
+
+        let to_i64 = |value: &C| {
+            value
+                .to_i64()
+                .ok_or_else(|| format!("{} does not fit in an i64", value))
+        };
+        let memory = proc.memory();
+        let cells = memory
+            .nonzero()
+            .iter()
+            .map(|(addr, value)| Ok((*addr, to_i64(value)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Execution {
+            state,
+            output: output.iter().map(to_i64).collect::<Result<_, _>>()?,
+            memory: Memory::from_nonzero(memory.extent(), &cells, memory.max_pages()),
+        })
+    }
+}
+
+/// A deliberately plain interpreter to check the others against, sharing no code with
+/// `Processor`. It fails rather than guessing on anything unusual, such as arithmetic overflow.
+///
+/// Memory is limited to `DEFAULT_MAX_PAGES` pages of `PAGE_SIZE` cells, counted the same way as
+/// in `Memory`, so that writes to far off addresses cost no more than they do there.
+pub fn reference(code: &[i64], input: &[i64]) -> Result<Execution, String> {
+    let mut memory = code.iter().cloned().enumerate().collect::<BTreeMap<_, _>>();
+    let mut pages = (0..code.len())
+        .step_by(PAGE_SIZE)
+        .map(|addr| addr / PAGE_SIZE)
+        .collect::<BTreeSet<_>>();
+    let mut extent = code.len();
+    let mut input = input.iter();
+    let mut output = Vec::new();
+    let mut pc = 0;
+    let mut rel = 0;
+
+    fn read(memory: &BTreeMap<usize, i64>, addr: usize) -> i64 {
+        memory.get(&addr).cloned().unwrap_or(0)
+    }
+
+    fn execution(
+        state: ProcessorState,
+        output: Vec<i64>,
+        memory: BTreeMap<usize, i64>,
+        extent: usize,
+    ) -> Execution {
+        let cells = memory
+            .into_iter()
+            .filter(|(_, value)| *value != 0)
+            .collect::<Vec<_>>();
+        Execution {
+            state,
+            output,
+            memory: Memory::from_nonzero(extent, &cells, DEFAULT_MAX_PAGES),
+        }
+    }
+
+    for _ in 0..REFERENCE_STEP_LIMIT {
+        let inst = read(&memory, pc);
+        let param = |memory: &BTreeMap<usize, i64>, n: u32| -> Result<usize, String> {
+            let raw = read(memory, pc + n as usize);
+            let addr = match inst / 10i64.pow(n + 1) % 10 {
+                0 => raw,
+                1 => (pc + n as usize) as i64,
+                2 => rel + raw,
+                mode => return Err(format!("Bad mode {} at {}", mode, pc)),
+            };
+            if addr < 0 {
+                Err(format!("Negative address {} at {}", addr, pc))
+            } else {
+                Ok(addr as usize)
+            }
+        };
+        let arg =
+            |memory: &BTreeMap<usize, i64>, n: u32| param(memory, n).map(|addr| read(memory, addr));
+        let overflow = || format!("Overflow at {}", pc);
+
+        let (addr, value) = match inst % 100 {
+            1 => (
+                param(&memory, 3)?,
+                arg(&memory, 1)?
+                    .checked_add(arg(&memory, 2)?)
+                    .ok_or_else(overflow)?,
+            ),
+            2 => (
+                param(&memory, 3)?,
+                arg(&memory, 1)?
+                    .checked_mul(arg(&memory, 2)?)
+                    .ok_or_else(overflow)?,
+            ),
+            3 => match input.next() {
+                Some(value) => (param(&memory, 1)?, *value),
+                None => return Ok(execution(ProcessorState::IoWait, output, memory, extent)),
+            },
+            4 => {
+                output.push(arg(&memory, 1)?);
+                pc += 2;
+                continue;
+            }
+            5 | 6 => {
+                if (arg(&memory, 1)? != 0) == (inst % 100 == 5) {
+                    pc = arg(&memory, 2)?
+                        .try_into()
+                        .map_err(|_| format!("Bad jump at {}", pc))?;
+                } else {
+                    pc += 3;
+                }
+                continue;
+            }
+            7 => (
+                param(&memory, 3)?,
+                (arg(&memory, 1)? < arg(&memory, 2)?) as i64,
+            ),
+            8 => (
+                param(&memory, 3)?,
+                (arg(&memory, 1)? == arg(&memory, 2)?) as i64,
+            ),
+            9 => {
+                rel = rel.checked_add(arg(&memory, 1)?).ok_or_else(overflow)?;
+                pc += 2;
+                continue;
+            }
+            99 => return Ok(execution(ProcessorState::Halted, output, memory, extent)),
+            _ => return Err(format!("Bad opcode {} at {}", inst, pc)),
+        };
+
+        if value != 0 && !pages.contains(&(addr / PAGE_SIZE)) {
+            if pages.len() >= DEFAULT_MAX_PAGES {
+                return Err(format!("Out of memory writing to {} at {}", addr, pc));
+            }
+            pages.insert(addr / PAGE_SIZE);
+        }
+        memory.insert(addr, value);
+        extent = extent.max(addr + 1);
+        pc += if inst % 100 == 3 { 2 } else { 4 };
+    }
+    Err("Did not halt".to_string())
+}
+
+/// The shape of the random programs generated for fuzzing.
+///
+/// Programs are a run of instructions ending in a halt, followed by a region of data cells.
+/// Every parameter refers to a data cell or holds an immediate value, and jumps only go forwards,
+/// so generated programs never modify their own code and always halt. When relative mode is
+/// allowed the relative base is set to the start of the data before anything else runs.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Generator {
+    /// The most instructions to generate, not counting the final halt
+    pub instructions: usize,
+    /// How many data cells follow the code
+    pub data: usize,
+    /// The range of initial data values, immediate values and input
+    pub values: RangeInclusive<i64>,
+    /// The instructions to choose from. Halts and relative base changes are added as needed.
+    pub opcodes: Vec<Opcode>,
+    /// The parameter modes to choose from. Jump targets are always immediate, and writes are
+    /// never immediate.
+    pub modes: Vec<ParamMode>,
+}
+
+impl Default for Generator {
+    fn default() -> Generator {
+        Generator {
+            instructions: 20,
+            data: 8,
+            values: -20..=20,
+            opcodes: Generator::body_opcodes(&Opcode::ALL),
+            modes: vec![
+                ParamMode::Position,
+                ParamMode::Immediate,
+                ParamMode::Relative,
+            ],
+        }
+    }
+}
+
+/// The ways in which a `Generator` can be set up so that it has nothing to generate
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum GeneratorError {
+    /// `opcodes` holds nothing but halts and relative base changes
+    NoOpcodes,
+    /// `values` is empty
+    NoValues,
+}
+
+impl Display for GeneratorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            GeneratorError::NoOpcodes => write!(f, "No instructions to generate"),
+            GeneratorError::NoValues => write!(f, "No values to generate"),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+impl Generator {
+    /// Check that there is something to generate. Modes need no checking, as position mode is
+    /// used whenever none of the allowed modes fit.
+    pub fn validate(&self) -> Result<(), GeneratorError> {
+        if self.values.is_empty() {
+            Err(GeneratorError::NoValues)
+        } else if Generator::body_opcodes(&self.opcodes).is_empty() {
+            Err(GeneratorError::NoOpcodes)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn body_opcodes(opcodes: &[Opcode]) -> Vec<Opcode> {
+        opcodes
+            .iter()
+            .cloned()
+            .filter(|opcode| *opcode != Opcode::Halt && *opcode != Opcode::Rel)
+            .collect()
+    }
+
+    /// A random program, which must only be asked for once the generator is known to be valid
+    fn generate(&self, rng: &mut Rng) -> Program {
+        let count = 1 + rng.below(self.instructions.max(1));
+        let opcodes = Generator::body_opcodes(&self.opcodes);
+        let write_modes = self
+            .modes
+            .iter()
+            .cloned()
+            .filter(|mode| *mode != ParamMode::Immediate)
+            .collect::<Vec<_>>();
+
+        let body = (0..count)
+            .map(|index| {
+                let opcode = opcodes[rng.below(opcodes.len())];
+                let params = (0..opcode.num_params())
+                    .map(|param| match opcode {
+                        Opcode::Jnz | Opcode::Jz if param == 1 => {
+                            Param::Target(index + 1 + rng.below(count - index))
+                        }
+                        _ if opcode.write_param() == Some(param) => {
+                            let mode = rng.choose(&write_modes).unwrap_or(ParamMode::Position);
+                            Param::Data(mode, rng.below(self.data.max(1)))
+                        }
+                        _ => match rng.choose(&self.modes).unwrap_or(ParamMode::Position) {
+                            ParamMode::Immediate => Param::Immediate(rng.range(&self.values)),
+                            mode => Param::Data(mode, rng.below(self.data.max(1))),
+                        },
+                    })
+                    .collect();
+                Inst { opcode, params }
+            })
+            .collect::<Vec<_>>();
+
+        let reads = body
+            .iter()
+            .filter(|inst| inst.opcode == Opcode::Read)
+            .count();
+        Program {
+            body,
+            data: (0..self.data.max(1))
+                .map(|_| rng.range(&self.values))
+                .collect(),
+            input: (0..reads).map(|_| rng.range(&self.values)).collect(),
+        }
+    }
+}
+
+/// A parameter of a generated instruction
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Param {
+    Immediate(i64),
+    /// A data cell, by its index in the data region, addressed in the given mode
+    Data(ParamMode, usize),
+    /// The start of another instruction, by its index in the body. One past the last
+    /// instruction is the final halt.
+    Target(usize),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Inst {
+    opcode: Opcode,
+    params: Vec<Param>,
+}
+
+/// A generated program, kept in a form that can be shrunk while staying valid
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Program {
+    body: Vec<Inst>,
+    data: Vec<i64>,
+    input: Vec<i64>,
+}
+
+impl Program {
+    fn code(&self) -> Vec<i64> {
+        let relative = self.params().any(|param| match param {
+            Param::Data(mode, _) => *mode == ParamMode::Relative,
+            _ => false,
+        });
+        let prologue = if relative { 2 } else { 0 };
+        let mut addrs = vec![prologue];
+        for inst in &self.body {
+            addrs.push(addrs.last().unwrap() + 1 + inst.params.len());
+        }
+        let data_start = *addrs.last().unwrap() as i64 + 1;
+
+        let mut code = Vec::new();
+        if relative {
+            code.extend(&[109, data_start]);
+        }
+        for inst in &self.body {
+            let mut raw = inst.opcode.code();
+            let mut values = Vec::new();
+            for (i, param) in inst.params.iter().enumerate() {
+                let (mode, value) = match *param {
+                    Param::Immediate(value) => (1, value),
+                    Param::Target(index) => (1, addrs[index] as i64),
+                    Param::Data(ParamMode::Position, index) => (0, data_start + index as i64),
+                    Param::Data(_, index) => (2, index as i64),
+                };
+                raw += mode * 10i64.pow(i as u32 + 2);
+                values.push(value);
+            }
+            code.push(raw);
+            code.extend(values);
+        }
+        code.push(Opcode::Halt.code());
+        code.extend(&self.data);
+        code
+    }
+
+    fn params(&self) -> impl Iterator<Item = &Param> {
+        self.body.iter().flat_map(|inst| inst.params.iter())
+    }
+
+    /// Every program one step simpler than this one, most drastic first
+    fn simplifications(&self) -> Vec<Program> {
+        let mut simpler = Vec::new();
+        for i in 0..self.body.len() {
+            let mut program = self.clone();
+            program.body.remove(i);
+            for inst in &mut program.body {
+                for param in &mut inst.params {
+                    if let Param::Target(index) = param {
+                        if *index > i {
+                            *index -= 1;
+                        }
+                    }
+                }
+            }
+            simpler.push(program);
+        }
+
+        let last = self.data.len() - 1;
+        let last_used = self.params().any(|param| match param {
+            Param::Data(_, index) => *index == last,
+            _ => false,
+        });
+        if last > 0 && !last_used {
+            let mut program = self.clone();
+            program.data.pop();
+            simpler.push(program);
+        }
+        if !self.input.is_empty() {
+            let mut program = self.clone();
+            program.input.pop();
+            simpler.push(program);
+        }
+
+        for i in 0..self.data.len() {
+            if self.data[i] != 0 {
+                let mut program = self.clone();
+                program.data[i] = 0;
+                simpler.push(program);
+            }
+        }
+        for i in 0..self.input.len() {
+            if self.input[i] != 0 {
+                let mut program = self.clone();
+                program.input[i] = 0;
+                simpler.push(program);
+            }
+        }
+        for (i, inst) in self.body.iter().enumerate() {
+            for (j, param) in inst.params.iter().enumerate() {
+                let replacement = match *param {
+                    Param::Immediate(value) if value != 0 => Param::Immediate(0),
+                    Param::Data(ParamMode::Relative, index) => {
+                        Param::Data(ParamMode::Position, index)
+                    }
+                    _ => continue,
+                };
+                let mut program = self.clone();
+                program.body[i].params[j] = replacement;
+                simpler.push(program);
+            }
+        }
+        simpler
+    }
+}
+
+/// A program on which an implementation disagreed with the reference
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Divergence {
+    pub implementation: String,
+    pub code: Vec<i64>,
+    pub input: Vec<i64>,
+    pub expected: Execution,
+    pub actual: Result<Execution, String>,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let join = |values: &[i64]| {
+            values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        writeln!(f, "{} diverged from the reference", self.implementation)?;
+        writeln!(f, "  program: {}", join(&self.code))?;
+        writeln!(f, "  input: {}", join(&self.input))?;
+        writeln!(f, "  expected: {:?}", self.expected)?;
+        write!(f, "  actual: {:?}", self.actual)
+    }
+}
+
+/// Something that can run an Intcode program, for comparison by a `Fuzzer`
+type Interpreter<'a> = Box<dyn Fn(&[i64], &[i64]) -> Result<Execution, String> + 'a>;
+
+/// Runs random programs on several Intcode implementations, looking for any that disagree
+/// with a reference implementation about the output, final memory or state a program stops in.
+///
+/// Programs the reference cannot run, for instance because they overflow its cells, are
+/// skipped. An implementation that panics is treated as disagreeing.
+pub struct Fuzzer<'a> {
+    pub generator: Generator,
+    /// Seed for the random programs, so that a run can be repeated exactly
+    pub seed: u64,
+    /// How many programs to try
+    pub iterations: usize,
+    reference: Interpreter<'a>,
+    implementations: Vec<(String, Interpreter<'a>)>,
+}
+
+impl<'a> Fuzzer<'a> {
+    /// A fuzzer comparing implementations against `reference`
+    pub fn new<F>(reference: F) -> Fuzzer<'a>
+    where
+        F: Fn(&[i64], &[i64]) -> Result<Execution, String> + 'a,
+    {
+        Fuzzer {
+            generator: Generator::default(),
+            seed: 0,
+            iterations: 1_000,
+            reference: Box::new(reference),
+            implementations: Vec::new(),
+        }
+    }
+
+    /// Add an implementation to compare against the reference
+    pub fn add<F>(&mut self, name: &str, implementation: F)
+    where
+        F: Fn(&[i64], &[i64]) -> Result<Execution, String> + 'a,
+    {
+        self.implementations
+            .push((name.to_string(), Box::new(implementation)));
+    }
+
+    /// Try `iterations` random programs, returning the first divergence found after shrinking
+    /// its program as far as possible while it still diverges
+    pub fn run(&self) -> Result<Option<Divergence>, GeneratorError> {
+        self.generator.validate()?;
+        let mut rng = Rng::new(self.seed);
+        for _ in 0..self.iterations {
+            let program = self.generator.generate(&mut rng);
+            if let Some(divergence) = self.check_program(&program, None) {
+                return Ok(Some(self.minimize(program, divergence)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Compare every implementation against the reference on a single program, returning the
+    /// first that disagrees. The program must halt or run out of input, or this will not return.
+    pub fn check(&self, code: &[i64], input: &[i64]) -> Option<Divergence> {
+        let expected = Fuzzer::call(&self.reference, code, input).ok()?;
+        self.implementations
+            .iter()
+            .find_map(|(name, implementation)| {
+                let actual = Fuzzer::call(implementation, code, input);
+                if actual.as_ref() == Ok(&expected) {
+                    None
+                } else {
+                    Some(Divergence {
+                        implementation: name.clone(),
+                        code: code.to_vec(),
+                        input: input.to_vec(),
+                        expected: expected.clone(),
+                        actual,
+                    })
+                }
+            })
+    }
+
+    /// Check a generated program, only against the named implementation if one is given
+    fn check_program(&self, program: &Program, only: Option<&str>) -> Option<Divergence> {
+        self.check(&program.code(), &program.input)
+            .filter(|divergence| only.is_none_or(|name| name == divergence.implementation))
+    }
+
+    fn minimize(&self, mut program: Program, mut divergence: Divergence) -> Divergence {
+        let name = divergence.implementation.clone();
+        'shrink: loop {
+            for simpler in program.simplifications() {
+                if let Some(found) = self.check_program(&simpler, Some(&name)) {
+                    program = simpler;
+                    divergence = found;
+                    continue 'shrink;
+                }
+            }
+            return divergence;
+        }
+    }
+
+    fn call(
+        implementation: &Interpreter,
+        code: &[i64],
+        input: &[i64],
+    ) -> Result<Execution, String> {
+        panic::catch_unwind(AssertUnwindSafe(|| implementation(code, input))).unwrap_or_else(
+            |payload| {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(format!("Panicked: {}", message))
+            },
+        )
+    }
+}
+
+/// A small xorshift generator, so that fuzzing needs no outside crates and is repeatable
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number from 0 up to but not including `n`
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A number in `range`, which must not be empty
+    fn range(&mut self, range: &RangeInclusive<i64>) -> i64 {
+        let span = *range.end() as i128 - *range.start() as i128 + 1;
+        if span > u64::MAX as i128 {
+            return self.next() as i64;
+        }
+        (*range.start() as i128 + (self.next() % span as u64) as i128) as i64
+    }
+
+    fn choose<T: Copy>(&mut self, items: &[T]) -> Option<T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(items[self.below(items.len())])
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::Disassembly;
+    use num::BigInt;
+
+    #[test]
+    fn test_processor_agrees_with_reference() {
+        let mut fuzzer = Fuzzer::new(reference);
+        fuzzer.iterations = 300;
+        fuzzer.add("cached", |code, input| {
+            Execution::processor::<i64>(code, input, true)
+        });
+        fuzzer.add("uncached", |code, input| {
+            Execution::processor::<i64>(code, input, false)
+        });
+        fuzzer.add("big int", |code, input| {
+            Execution::processor::<BigInt>(code, input, true)
+        });
+        if let Some(divergence) = fuzzer.run().unwrap() {
+            panic!("{}", divergence);
+        }
+    }
+
+    #[test]
+    fn test_sparse_write() {
+        let code = [1101, 1, 1, 1_000_000_000_000, 99];
+        let expected = reference(&code, &[]).unwrap();
+        assert_eq!(expected.memory.extent(), 1_000_000_000_001);
+        assert_eq!(expected.memory.get(1_000_000_000_000), 2);
+        assert_eq!(Execution::processor::<i64>(&code, &[], true), Ok(expected));
+    }
+
+    #[test]
+    fn test_generated_programs_halt() {
+        let generator = Generator::default();
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let program = generator.generate(&mut rng);
+            if let Ok(execution) = reference(&program.code(), &program.input) {
+                assert_eq!(execution.state, ProcessorState::Halted);
+            }
+        }
+    }
+
+    #[test]
+    fn test_generator_config() {
+        let mut fuzzer = Fuzzer::new(reference);
+        fuzzer.iterations = 20;
+        fuzzer.add("cached", |code, input| {
+            Execution::processor::<i64>(code, input, true)
+        });
+
+        fuzzer.generator.opcodes = vec![Opcode::Halt, Opcode::Rel];
+        assert_eq!(fuzzer.run(), Err(GeneratorError::NoOpcodes));
+        fuzzer.generator.opcodes = vec![Opcode::Add];
+        fuzzer.generator.values = RangeInclusive::new(1, 0);
+        assert_eq!(fuzzer.run(), Err(GeneratorError::NoValues));
+
+        // Writes fall back to position mode, and values may span every i64
+        fuzzer.generator.modes = vec![ParamMode::Immediate];
+        fuzzer.generator.values = i64::MIN..=i64::MAX;
+        assert_eq!(fuzzer.run(), Ok(None));
+    }
+
+    #[test]
+    fn test_divergence_minimized() {
+        // Loses every output after the first
+        let mut fuzzer = Fuzzer::new(reference);
+        fuzzer.add("forgetful", |code, input| {
+            reference(code, input).map(|mut execution| {
+                execution.output.truncate(1);
+                execution
+            })
+        });
+        let divergence = fuzzer.run().unwrap().unwrap();
+        assert_eq!(divergence.implementation, "forgetful");
+        assert_eq!(divergence.expected.output, vec![0, 0]);
+        assert_eq!(
+            Disassembly::new(&divergence.code).source(),
+            "output #0\noutput #0\nhalt\ndb 0\n"
+        );
+    }
+
+    #[test]
+    fn test_panic_is_divergence() {
+        let mut fuzzer = Fuzzer::new(reference);
+        fuzzer.add("broken", |_, _| panic!("Not implemented"));
+        let divergence = fuzzer.check(&[99], &[]).unwrap();
+        assert_eq!(
+            divergence.actual,
+            Err("Panicked: Not implemented".to_string())
+        );
+    }
+}
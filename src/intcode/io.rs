@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Where a `Processor` gets the values for its input instructions
+pub trait Input<C = i64> {
+    /// The next value to be read, or `None` if there is nothing to read right now, in which case
+    /// the processor suspends with `ProcessorState::IoWait`
+    fn read(&mut self) -> Option<C>;
+}
+
+/// Where a `Processor` sends the values from its output instructions
+pub trait Output<C = i64> {
+    fn write(&mut self, value: C);
+}
+
+impl<C> Input<C> for VecDeque<C> {
+    fn read(&mut self) -> Option<C> {
+        self.pop_front()
+    }
+}
+
+impl<C> Output<C> for VecDeque<C> {
+    fn write(&mut self, value: C) {
+        self.push_back(value);
+    }
+}
+
+impl<C> Output<C> for Vec<C> {
+    fn write(&mut self, value: C) {
+        self.push(value);
+    }
+}
+
+/// Reading from a channel blocks until a value arrives. Once every sender has been dropped, the
+/// processor suspends as if waiting for input.
+impl<C> Input<C> for Receiver<C> {
+    fn read(&mut self) -> Option<C> {
+        self.recv().ok()
+    }
+}
+
+/// Values written after the receiver has been dropped are discarded
+impl<C> Output<C> for Sender<C> {
+    fn write(&mut self, value: C) {
+        let _ = self.send(value);
+    }
+}
+
+impl<C, I: Input<C> + ?Sized> Input<C> for &mut I {
+    fn read(&mut self) -> Option<C> {
+        (**self).read()
+    }
+}
+
+impl<C, O: Output<C> + ?Sized> Output<C> for &mut O {
+    fn write(&mut self, value: C) {
+        (**self).write(value)
+    }
+}
+
+/// An input that calls a closure for each value
+pub struct InputFn<F>(pub F);
+
+impl<C, F: FnMut() -> Option<C>> Input<C> for InputFn<F> {
+    fn read(&mut self) -> Option<C> {
+        (self.0)()
+    }
+}
+
+/// An output that calls a closure with each value
+pub struct OutputFn<F>(pub F);
+
+impl<C, F: FnMut(C)> Output<C> for OutputFn<F> {
+    fn write(&mut self, value: C) {
+        (self.0)(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{IntcodeError, Processor, ProcessorState};
+    use std::sync::mpsc::channel;
+
+    /// Reads two numbers and outputs their sum, forever
+    fn adder() -> Vec<i64> {
+        vec![3, 13, 3, 14, 1, 13, 14, 13, 4, 13, 1105, 1, 0, 0, 0]
+    }
+
+    #[test]
+    fn test_queue_io() {
+        let mut input = deque!(1, 2, 3);
+        let mut output = Vec::new();
+        assert_eq!(
+            Processor::new(adder()).run(&mut input, &mut output),
+            Ok(ProcessorState::IoWait)
+        );
+        assert_eq!(input, deque!());
+        assert_eq!(output, vec![3]);
+    }
+
+    #[test]
+    fn test_closure_io() {
+        let mut next = 0;
+        let mut total = 0;
+        let result = Processor::new(adder()).run(
+            &mut InputFn(|| {
+                next += 1;
+                if next <= 6 {
+                    Some(next)
+                } else {
+                    None
+                }
+            }),
+            &mut OutputFn(|value| total += value),
+        );
+        assert_eq!(result, Ok(ProcessorState::IoWait));
+        assert_eq!(total, 21);
+    }
+
+    #[test]
+    fn test_threaded_pipeline() {
+        let (to_first, first_input) = channel();
+        let (first_output, second_input) = channel();
+        let (second_output, results) = channel();
+
+        let first = Processor::new(adder()).spawn(first_input, first_output);
+        let second = Processor::new(adder()).spawn(second_input, second_output);
+
+        for value in 1..=8 {
+            to_first.send(value).unwrap();
+        }
+        drop(to_first);
+
+        assert_eq!(first.join().unwrap(), Ok(ProcessorState::IoWait));
+        assert_eq!(second.join().unwrap(), Ok(ProcessorState::IoWait));
+        assert_eq!(results.iter().collect::<Vec<i64>>(), vec![10, 26]);
+    }
+
+    #[test]
+    fn test_thread_reports_error() {
+        let (_, input) = channel();
+        let (output, _) = channel();
+        assert_eq!(
+            Processor::new(vec![42])
+                .spawn(input, output)
+                .join()
+                .unwrap(),
+            Err(IntcodeError::UnknownOpcode { pc: 0, opcode: 42 })
+        );
+    }
+}
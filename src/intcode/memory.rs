@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+
+use super::Cell;
+
+/// The number of cells allocated together whenever a previously untouched region is written
+pub const PAGE_SIZE: usize = 1024;
+
+/// The number of pages a `Memory` may allocate unless told otherwise, 512 MiB worth of cells
+pub const DEFAULT_MAX_PAGES: usize = 64 * 1024;
+
+/// The address space of an Intcode machine.
+///
+/// Cells are stored in fixed-size pages that are only allocated once something non-zero is
+/// written to them, so a program may use addresses as large as it likes while only paying for
+/// the regions it actually touches. Cells that were never written read as 0.
+#[derive(Clone)]
+pub struct Memory<C: Cell = i64> {
+    pages: HashMap<usize, Box<[C]>>,
+    max_pages: usize,
+    extent: usize,
+}
+
+impl<C: Cell> Memory<C> {
+    /// Create a memory holding `code` at address 0, limited to `DEFAULT_MAX_PAGES`
+    pub fn new(code: &[C]) -> Memory<C> {
+        Memory::with_limit(code, DEFAULT_MAX_PAGES)
+    }
+
+    /// Create a memory holding `code` at address 0 that may allocate at most `max_pages` pages.
+    ///
+    /// The initial program is always loaded, even if it alone exceeds the limit.
+    pub fn with_limit(code: &[C], max_pages: usize) -> Memory<C> {
+        let mut pages = HashMap::new();
+        for (page, chunk) in code.chunks(PAGE_SIZE).enumerate() {
+            let mut cells = Memory::empty_page();
+            cells[..chunk.len()].clone_from_slice(chunk);
+            pages.insert(page, cells);
+        }
+        Memory {
+            pages,
+            max_pages,
+            extent: code.len(),
+        }
+    }
+
+    /// Create a memory of the given extent in which `cells` hold the listed values and every
+    /// other address holds 0, the counterpart to `nonzero`
+    pub fn from_nonzero(extent: usize, cells: &[(usize, C)], max_pages: usize) -> Memory<C> {
+        let mut pages: HashMap<usize, Box<[C]>> = HashMap::new();
+        for (addr, value) in cells {
+            pages
+                .entry(addr / PAGE_SIZE)
+                .or_insert_with(Memory::empty_page)[addr % PAGE_SIZE] = value.clone();
+        }
+        Memory {
+            pages,
+            max_pages,
+            extent: cells
+                .iter()
+                .map(|(addr, _)| addr + 1)
+                .max()
+                .unwrap_or(0)
+                .max(extent),
+        }
+    }
+
+    /// The value stored at `addr`
+    pub fn get(&self, addr: usize) -> C {
+        match self.pages.get(&(addr / PAGE_SIZE)) {
+            Some(cells) => cells[addr % PAGE_SIZE].clone(),
+            None => C::zero(),
+        }
+    }
+
+    fn empty_page() -> Box<[C]> {
+        vec![C::zero(); PAGE_SIZE].into_boxed_slice()
+    }
+
+    /// Whether the page numbered `page` holds the same values as `cells`
+    fn page_matches(&self, page: usize, cells: &[C]) -> bool {
+        match self.pages.get(&page) {
+            Some(own) => own[..] == cells[..],
+            None => cells.iter().all(Cell::is_zero),
+        }
+    }
+
+    /// Whether any value can be stored at `addr`, because its page is already allocated or there
+    /// is room for one more
+    pub(crate) fn writable(&self, addr: usize) -> bool {
+        self.pages.contains_key(&(addr / PAGE_SIZE)) || self.pages.len() < self.max_pages
+    }
+
+    /// Store `value` at `addr`, allocating a new page if needed.
+    ///
+    /// Returns false, leaving memory unchanged, if that would take the number of allocated pages
+    /// beyond the limit.
+    pub fn set(&mut self, addr: usize, value: C) -> bool {
+        let page = addr / PAGE_SIZE;
+        if !self.pages.contains_key(&page) {
+            if value.is_zero() {
+                self.extent = self.extent.max(addr + 1);
+                return true;
+            }
+            if self.pages.len() >= self.max_pages {
+                return false;
+            }
+            self.pages.insert(page, Memory::empty_page());
+        }
+        self.pages.get_mut(&page).unwrap()[addr % PAGE_SIZE] = value;
+        self.extent = self.extent.max(addr + 1);
+        true
+    }
+
+    /// One past the highest address that has been loaded or written
+    pub fn extent(&self) -> usize {
+        self.extent
+    }
+
+    /// Shrink the extent back to what it was before some writes were undone. Pages are left
+    /// allocated, so the cells beyond the new extent must already have been set back to 0.
+    pub(crate) fn restore_extent(&mut self, extent: usize) {
+        self.extent = extent;
+    }
+
+    /// The number of pages currently allocated
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// The maximum number of pages that may be allocated
+    pub fn max_pages(&self) -> usize {
+        self.max_pages
+    }
+
+    /// The addresses and values of every allocated cell holding something other than 0, in
+    /// address order
+    pub fn nonzero(&self) -> Vec<(usize, C)> {
+        let mut pages = self.pages.keys().cloned().collect::<Vec<usize>>();
+        pages.sort();
+        pages
+            .into_iter()
+            .flat_map(|page| {
+                self.pages[&page]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, value)| !value.is_zero())
+                    .map(move |(offset, value)| (page * PAGE_SIZE + offset, value.clone()))
+            })
+            .collect()
+    }
+
+    /// Copy the cells from 0 up to the extent into a contiguous vector. This allocates the whole
+    /// extent however little of it is used, so compare memories directly or through `nonzero`.
+    pub fn to_vec(&self) -> Vec<C> {
+        (0..self.extent).map(|addr| self.get(addr)).collect()
+    }
+}
+
+impl<C: Cell> PartialEq for Memory<C> {
+    /// Two memories are equal if every address reads the same and they have the same extent,
+    /// regardless of how their pages happen to be allocated
+    fn eq(&self, other: &Memory<C>) -> bool {
+        self.extent == other.extent
+            && self
+                .pages
+                .iter()
+                .all(|(page, cells)| other.page_matches(*page, cells))
+            && other
+                .pages
+                .iter()
+                .all(|(page, cells)| self.page_matches(*page, cells))
+    }
+}
+
+impl<C: Cell> Eq for Memory<C> {}
+
+impl<C: Cell> Debug for Memory<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Memory")
+            .field("extent", &self.extent)
+            .field("pages", &self.pages.len())
+            .field("max_pages", &self.max_pages)
+            .field("nonzero", &self.nonzero())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let code = (0..2500).collect::<Vec<i64>>();
+        let memory = Memory::new(&code);
+        assert_eq!(memory.pages(), 3);
+        assert_eq!(memory.extent(), 2500);
+        assert_eq!(memory.get(1234), 1234);
+        assert_eq!(memory.get(2500), 0);
+        assert_eq!(memory.to_vec(), code);
+    }
+
+    #[test]
+    fn test_unwritten_reads_zero() {
+        let memory = Memory::new(&[1, 2, 3]);
+        assert_eq!(memory.get(1_000_000_000_000), 0);
+        assert_eq!(memory.pages(), 1);
+    }
+
+    #[test]
+    fn test_sparse_write() {
+        let mut memory = Memory::new(&[1, 2, 3]);
+        assert!(memory.set(1_000_000_000_000, 42));
+        assert_eq!(memory.get(1_000_000_000_000), 42);
+        assert_eq!(memory.get(1_000_000_000_001), 0);
+        assert_eq!(memory.pages(), 2);
+        assert_eq!(memory.extent(), 1_000_000_000_001);
+        assert_eq!(
+            memory.nonzero(),
+            vec![(0, 1), (1, 2), (2, 3), (1_000_000_000_000, 42)]
+        );
+    }
+
+    #[test]
+    fn test_zero_write_does_not_allocate() {
+        let mut memory = Memory::with_limit(&[1], 1);
+        assert!(memory.set(5000, 0));
+        assert_eq!(memory.pages(), 1);
+        assert_eq!(memory.extent(), 5001);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut memory = Memory::with_limit(&[1], 2);
+        assert!(memory.set(5000, 1));
+        assert!(!memory.set(10_000, 1));
+        assert_eq!(memory.get(10_000), 0);
+        assert!(memory.set(5001, 1));
+        assert_eq!(memory.pages(), 2);
+    }
+
+    #[test]
+    fn test_from_nonzero() {
+        let mut memory = Memory::new(&[1, 0, 3]);
+        memory.set(1_000_000, 4);
+        memory.set(2_000_000, 0);
+        assert_eq!(
+            Memory::from_nonzero(memory.extent(), &memory.nonzero(), DEFAULT_MAX_PAGES),
+            memory
+        );
+    }
+
+    #[test]
+    fn test_equality_ignores_allocation() {
+        let mut a = Memory::new(&[1, 2]);
+        let b = Memory::new(&[1, 2]);
+        a.set(5000, 7);
+        assert_ne!(a, b);
+        a.set(5000, 0);
+        assert_ne!(a, b);
+        let mut b = b;
+        b.set(5000, 0);
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,62 @@
+mod amplifier;
+mod ascii;
+mod asm;
+mod cell;
+mod cfg;
+mod compile;
+mod debugger;
+mod decode;
+mod device;
+mod disasm;
+mod error;
+mod fuzz;
+mod io;
+mod memory;
+mod network;
+mod opcode;
+mod processor;
+mod profile;
+mod replay;
+mod snapshot;
+mod solver;
+mod symbolic;
+mod trace;
+mod undo;
+
+pub use self::amplifier::{PhaseSearch, PhaseSetting};
+pub use self::ascii::{
+    encode_ascii, AsciiChunk, AsciiInput, AsciiOutput, AsciiTerminal, TerminalError,
+};
+pub use self::asm::{assemble, AsmError};
+pub use self::cell::Cell;
+pub use self::cfg::{Block, CodeWrite, ControlFlowGraph, Edge, EdgeKind};
+pub use self::compile::{compile, Runtime};
+pub use self::debugger::{Debugger, StopReason};
+pub use self::device::{Device, Machine};
+pub use self::disasm::{Disassembly, Instruction, Line, Operand};
+pub use self::error::IntcodeError;
+pub use self::fuzz::{reference, Divergence, Execution, Fuzzer, Generator, GeneratorError};
+pub use self::io::{Input, InputFn, Output, OutputFn};
+pub use self::memory::{Memory, DEFAULT_MAX_PAGES, PAGE_SIZE};
+pub use self::network::{
+    Network, NetworkError, NetworkOutcome, NetworkState, NodeStatus, Topology, TopologyError,
+};
+pub use self::opcode::Opcode;
+pub use self::processor::{ParamMode, Processor, ProcessorState};
+pub use self::profile::Profiler;
+pub use self::replay::{Mismatch, Recorder, Session, SessionError, SessionEvent, SessionMode};
+pub use self::snapshot::{Snapshot, SnapshotError};
+pub use self::solver::{Affine, GoalSeek, Outcome, SearchError, Target};
+pub use self::symbolic::{Condition, Expr, Path, PathEnd, SymbolicExecutor, Var};
+pub use self::trace::{NoObserver, Observer, TraceEvent, TracePrinter, TraceRecorder};
+pub use self::undo::Undo;
+
+use std::num::ParseIntError;
+
+/// Parse a comma-separated Intcode image, as found in the puzzle input files
+pub fn parse_program(text: &str) -> Result<Vec<i64>, ParseIntError> {
+    text.trim()
+        .split(',')
+        .map(|item| item.trim().parse::<i64>())
+        .collect()
+}
@@ -0,0 +1,630 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use super::{Cell, IntcodeError, Memory, Processor, ProcessorState};
+
+/// How the nodes of a `Network` are connected. Every value a node outputs is delivered to each
+/// of its successors, and a node with several predecessors reads their values in the order they
+/// were produced.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Topology {
+    /// Node `i` feeds node `i + 1`, and the result is the output of the last node
+    Chain(usize),
+    /// A chain whose last node also feeds back into the first
+    Ring(usize),
+    /// Node 0 feeds every other node, and the result is the output of node 0
+    FanOut(usize),
+    /// Any set of directed `(from, to)` edges between `nodes` nodes
+    Graph {
+        nodes: usize,
+        edges: Vec<(usize, usize)>,
+        result: usize,
+    },
+}
+
+impl Topology {
+    pub fn nodes(&self) -> usize {
+        match self {
+            Topology::Chain(nodes) | Topology::Ring(nodes) | Topology::FanOut(nodes) => *nodes,
+            Topology::Graph { nodes, .. } => *nodes,
+        }
+    }
+
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Chain(nodes) => (1..*nodes).map(|to| (to - 1, to)).collect(),
+            Topology::Ring(nodes) => (0..*nodes).map(|from| (from, (from + 1) % nodes)).collect(),
+            Topology::FanOut(nodes) => (1..*nodes).map(|to| (0, to)).collect(),
+            Topology::Graph { edges, .. } => edges.clone(),
+        }
+    }
+
+    /// Check that the topology has at least one node and that every edge and the result refer
+    /// to nodes that exist
+    pub fn validate(&self) -> Result<(), TopologyError> {
+        let nodes = self.nodes();
+        if nodes == 0 {
+            return Err(TopologyError::Empty);
+        }
+        let endpoints = self
+            .edges()
+            .into_iter()
+            .flat_map(|(from, to)| vec![from, to]);
+        match std::iter::once(self.result())
+            .chain(endpoints)
+            .find(|node| *node >= nodes)
+        {
+            Some(node) => Err(TopologyError::UnknownNode { node, nodes }),
+            None => Ok(()),
+        }
+    }
+
+    /// The node whose output is the result of running the network
+    pub fn result(&self) -> usize {
+        match self {
+            Topology::Chain(nodes) | Topology::Ring(nodes) => nodes.saturating_sub(1),
+            Topology::FanOut(_) => 0,
+            Topology::Graph { result, .. } => *result,
+        }
+    }
+}
+
+/// How many instructions each node may execute before the scheduler moves on to the next one
+const SLICE: u64 = 1000;
+
+/// How a network run came to an end
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NetworkState<C: Cell = i64> {
+    /// Every node halted
+    Halted,
+    /// The listed nodes are waiting for input that no other node can provide
+    Deadlocked {
+        waiting: Vec<usize>,
+        nodes: Vec<NodeStatus<C>>,
+    },
+    /// The network returned to a state it had already been in, so it would keep cycling through
+    /// the same `period` scheduling rounds forever without halting
+    Livelocked {
+        period: usize,
+        nodes: Vec<NodeStatus<C>>,
+    },
+}
+
+impl<C: Cell> Display for NetworkState<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let nodes = match self {
+            NetworkState::Halted => return write!(f, "All nodes halted"),
+            NetworkState::Deadlocked { waiting, nodes } => {
+                writeln!(
+                    f,
+                    "Deadlock: nodes {:?} are waiting for input that will never arrive",
+                    waiting
+                )?;
+                nodes
+            }
+            NetworkState::Livelocked { period, nodes } => {
+                writeln!(
+                    f,
+                    "Livelock: the network repeats itself every {} rounds",
+                    period
+                )?;
+                nodes
+            }
+        };
+        for status in nodes {
+            writeln!(f, "  {}", status)?;
+        }
+        Ok(())
+    }
+}
+
+/// A summary of one node, used to explain why a network stopped
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NodeStatus<C: Cell = i64> {
+    pub node: usize,
+    pub pc: usize,
+    /// The state the node last stopped in, or `None` if it was still running
+    pub state: Option<ProcessorState>,
+    /// Input queued for the node that it has yet to read
+    pub pending: Vec<C>,
+}
+
+impl<C: Cell> Display for NodeStatus<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = match self.state {
+            None => "running",
+            Some(ProcessorState::IoWait) => "waiting",
+            Some(ProcessorState::Halted) => "halted",
+            Some(ProcessorState::BudgetExhausted) => "running",
+        };
+        write!(
+            f,
+            "node {}: {} at pc {}, pending input {:?}",
+            self.node, state, self.pc, self.pending
+        )
+    }
+}
+
+/// The result of running a `Network`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NetworkOutcome<C: Cell = i64> {
+    pub state: NetworkState<C>,
+    /// Everything each node output, indexed by node
+    pub outputs: Vec<Vec<C>>,
+    /// The node designated by the topology as holding the result
+    pub result_node: usize,
+}
+
+impl<C: Cell> NetworkOutcome<C> {
+    /// The last value output by the result node
+    pub fn result(&self) -> Option<&C> {
+        self.outputs[self.result_node].last()
+    }
+}
+
+/// The ways in which using a network can fail
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NetworkError {
+    /// A node of the network failed to execute
+    Node { node: usize, error: IntcodeError },
+    /// Input was fed to `node`, but there are only `nodes` nodes
+    UnknownNode { node: usize, nodes: usize },
+}
+
+impl Display for NetworkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NetworkError::Node { node, error } => write!(f, "Node {}: {}", node, error),
+            NetworkError::UnknownNode { node, nodes } => {
+                write!(f, "Node {} does not exist in a network of {}", node, nodes)
+            }
+        }
+    }
+}
+
+impl Error for NetworkError {}
+
+/// The ways in which a network can be set up wrongly
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TopologyError {
+    /// The topology has no nodes, so there is nothing to give a result
+    Empty,
+    /// An edge or the result refers to `node`, but there are only `nodes` nodes
+    UnknownNode { node: usize, nodes: usize },
+    /// The topology has `expected` nodes but `found` processors were given for them
+    WrongProcessorCount { expected: usize, found: usize },
+}
+
+impl Display for TopologyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TopologyError::Empty => write!(f, "Topology has no nodes"),
+            TopologyError::UnknownNode { node, nodes } => {
+                write!(f, "Node {} does not exist in a topology of {}", node, nodes)
+            }
+            TopologyError::WrongProcessorCount { expected, found } => write!(
+                f,
+                "Topology needs {} processors but got {}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl Error for TopologyError {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Node<C: Cell> {
+    proc: Processor<C>,
+    input: VecDeque<C>,
+    /// The state the node last stopped in, or `None` if it has not stopped since it last ran
+    state: Option<ProcessorState>,
+    successors: Vec<usize>,
+}
+
+impl<C: Cell> Node<C> {
+    fn runnable(&self) -> bool {
+        match self.state {
+            None => true,
+            Some(ProcessorState::IoWait) => !self.input.is_empty(),
+            Some(ProcessorState::Halted) => false,
+            Some(ProcessorState::BudgetExhausted) => true,
+        }
+    }
+}
+
+/// A set of Intcode machines wired together.
+///
+/// Nodes take turns on the current thread, each executing a bounded slice of instructions per
+/// round, so the outcome does not depend on thread scheduling and a run always ends: either
+/// every node halts, the nodes still running are all starved of input (a deadlock), or the whole
+/// network comes back to a state it was in before (a livelock).
+#[derive(Debug, Clone)]
+pub struct Network<C: Cell = i64> {
+    nodes: Vec<Node<C>>,
+    outputs: Vec<Vec<C>>,
+    result_node: usize,
+}
+
+impl<C: Cell> Network<C> {
+    /// Create a network in which every node runs its own copy of `code`, provided `topology` is
+    /// valid
+    pub fn new(code: &[C], topology: &Topology) -> Result<Network<C>, TopologyError> {
+        let procs = (0..topology.nodes())
+            .map(|_| Processor::with_memory(Memory::new(code)))
+            .collect();
+        Network::with_processors(procs, topology)
+    }
+
+    /// Create a network from one processor per node of `topology`, which must be valid. Each
+    /// processor's instruction budget is replaced with the scheduler's time slice.
+    pub fn with_processors(
+        procs: Vec<Processor<C>>,
+        topology: &Topology,
+    ) -> Result<Network<C>, TopologyError> {
+        topology.validate()?;
+        if procs.len() != topology.nodes() {
+            return Err(TopologyError::WrongProcessorCount {
+                expected: topology.nodes(),
+                found: procs.len(),
+            });
+        }
+        let mut nodes = procs
+            .into_iter()
+            .map(|mut proc| {
+                proc.set_instruction_budget(Some(SLICE));
+                Node {
+                    proc,
+                    input: VecDeque::new(),
+                    state: None,
+                    successors: Vec::new(),
+                }
+            })
+            .collect::<Vec<Node<C>>>();
+        for (from, to) in topology.edges() {
+            nodes[from].successors.push(to);
+        }
+
+        Ok(Network {
+            outputs: vec![Vec::new(); nodes.len()],
+            nodes,
+            result_node: topology.result(),
+        })
+    }
+
+    /// Queue initial input for `node`, such as a phase setting
+    pub fn feed<I: IntoIterator<Item = C>>(
+        &mut self,
+        node: usize,
+        values: I,
+    ) -> Result<(), NetworkError> {
+        let nodes = self.nodes.len();
+        self.nodes
+            .get_mut(node)
+            .ok_or(NetworkError::UnknownNode { node, nodes })?
+            .input
+            .extend(values);
+        Ok(())
+    }
+
+    /// Run the nodes until they have all halted, deadlocked or livelocked.
+    ///
+    /// Livelocks are found with Brent's cycle detection over the sequence of states the network
+    /// is in between rounds, so they are detected exactly, holding on to only one earlier state.
+    pub fn run(&mut self) -> Result<NetworkOutcome<C>, NetworkError> {
+        let mut saved = self.nodes.clone();
+        let mut power = 1;
+        let mut period = 0;
+
+        let state = loop {
+            if !self.nodes.iter().any(Node::runnable) {
+                break self.stopped_state();
+            }
+            self.round()?;
+
+            period += 1;
+            if self.nodes == saved {
+                break NetworkState::Livelocked {
+                    period,
+                    nodes: self.statuses(),
+                };
+            }
+            if period == power {
+                saved = self.nodes.clone();
+                power *= 2;
+                period = 0;
+            }
+        };
+
+        Ok(NetworkOutcome {
+            state,
+            outputs: self.outputs.clone(),
+            result_node: self.result_node,
+        })
+    }
+
+    /// Give every runnable node a slice of instructions, delivering its output as it goes
+    fn round(&mut self) -> Result<(), NetworkError> {
+        for node in 0..self.nodes.len() {
+            if !self.nodes[node].runnable() {
+                continue;
+            }
+            self.nodes[node].state = None;
+
+            let mut output = Vec::new();
+            let Node { proc, input, .. } = &mut self.nodes[node];
+            let state = proc
+                .run(input, &mut output)
+                .map_err(|error| NetworkError::Node { node, error })?;
+            self.nodes[node].state = match state {
+                ProcessorState::BudgetExhausted => None,
+                state => Some(state),
+            };
+
+            for to in self.nodes[node].successors.clone() {
+                self.nodes[to].input.extend(output.iter().cloned());
+            }
+            self.outputs[node].extend(output);
+        }
+        Ok(())
+    }
+
+    fn stopped_state(&self) -> NetworkState<C> {
+        let waiting = (0..self.nodes.len())
+            .filter(|node| self.nodes[*node].state != Some(ProcessorState::Halted))
+            .collect::<Vec<usize>>();
+        if waiting.is_empty() {
+            NetworkState::Halted
+        } else {
+            NetworkState::Deadlocked {
+                waiting,
+                nodes: self.statuses(),
+            }
+        }
+    }
+
+    fn statuses(&self) -> Vec<NodeStatus<C>> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(node, status)| NodeStatus {
+                node,
+                pc: status.proc.pc(),
+                state: status.state.clone(),
+                pending: status.input.iter().cloned().collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::assemble;
+
+    /// Outputs its phase setting plus ten times each value it reads, until it reads a 0
+    fn amplifier() -> Vec<i64> {
+        assemble(
+            "
+                read [phase]
+            loop:
+                read [value]
+                jz [value], #done
+                mul [value], #10, [value]
+                add [value], [phase], [value]
+                output [value]
+                jnz #1, #loop
+            done:
+                halt
+            phase: db 0
+            value: db 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_topology_edges() {
+        assert_eq!(Topology::Chain(3).edges(), vec![(0, 1), (1, 2)]);
+        assert_eq!(Topology::Ring(3).edges(), vec![(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(Topology::FanOut(3).edges(), vec![(0, 1), (0, 2)]);
+        assert_eq!(
+            (
+                Topology::Chain(3).result(),
+                Topology::Ring(3).result(),
+                Topology::FanOut(3).result()
+            ),
+            (2, 2, 0)
+        );
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut network = Network::new(&amplifier(), &Topology::Chain(3)).unwrap();
+        for (node, phase) in vec![1, 2, 3].into_iter().enumerate() {
+            network.feed(node, vec![phase]).unwrap();
+        }
+        network.feed(0, vec![4, 0]).unwrap();
+        let outcome = network.run().unwrap();
+        match &outcome.state {
+            NetworkState::Deadlocked { waiting, .. } => assert_eq!(*waiting, vec![1, 2]),
+            other => panic!("Unexpected state {:?}", other),
+        }
+        assert_eq!(outcome.outputs, vec![vec![41], vec![412], vec![4123]]);
+        assert_eq!(outcome.result(), Some(&4123));
+    }
+
+    #[test]
+    fn test_ring_until_halt() {
+        let code = crate::intcode::parse_program(
+            "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5",
+        )
+        .unwrap();
+        let mut network = Network::new(&code, &Topology::Ring(5)).unwrap();
+        for (node, phase) in vec![9, 8, 7, 6, 5].into_iter().enumerate() {
+            network.feed(node, vec![phase]).unwrap();
+        }
+        network.feed(0, vec![0]).unwrap();
+        let outcome = network.run().unwrap();
+        assert_eq!(outcome.state, NetworkState::Halted);
+        assert_eq!(outcome.result(), Some(&139629729));
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let echo = vec![3, 7, 4, 7, 1105, 1, 0, 0];
+        let mut network = Network::new(&echo, &Topology::FanOut(3)).unwrap();
+        network.feed(0, vec![5, 6]).unwrap();
+        let outcome = network.run().unwrap();
+        assert_eq!(outcome.outputs, vec![vec![5, 6], vec![5, 6], vec![5, 6]]);
+        assert_eq!(outcome.result(), Some(&6));
+    }
+
+    #[test]
+    fn test_graph_merges_inputs() {
+        let topology = Topology::Graph {
+            nodes: 3,
+            edges: vec![(0, 2), (1, 2)],
+            result: 2,
+        };
+        let mut network = Network::new(&amplifier(), &topology).unwrap();
+        network.feed(0, vec![1, 2, 0]).unwrap();
+        network.feed(1, vec![3, 4, 0]).unwrap();
+        network.feed(2, vec![0]).unwrap();
+        let outcome = network.run().unwrap();
+        assert_eq!(outcome.outputs[2], vec![210, 430]);
+        match &outcome.state {
+            NetworkState::Deadlocked { waiting, .. } => assert_eq!(*waiting, vec![2]),
+            other => panic!("Unexpected state {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_feed_unknown_node() {
+        let mut network = Network::new(&amplifier(), &Topology::Chain(2)).unwrap();
+        assert_eq!(
+            network.feed(2, vec![1]),
+            Err(NetworkError::UnknownNode { node: 2, nodes: 2 })
+        );
+    }
+
+    #[test]
+    fn test_invalid_topology() {
+        let code = amplifier();
+        assert_eq!(
+            Network::new(&code, &Topology::Ring(0)).err(),
+            Some(TopologyError::Empty)
+        );
+        let graph = Topology::Graph {
+            nodes: 2,
+            edges: vec![(0, 1), (1, 2)],
+            result: 1,
+        };
+        assert_eq!(
+            Network::new(&code, &graph).err(),
+            Some(TopologyError::UnknownNode { node: 2, nodes: 2 })
+        );
+        let graph = Topology::Graph {
+            nodes: 2,
+            edges: vec![(0, 1)],
+            result: 5,
+        };
+        assert_eq!(
+            Network::new(&code, &graph).err(),
+            Some(TopologyError::UnknownNode { node: 5, nodes: 2 })
+        );
+        assert_eq!(
+            Network::with_processors(vec![Processor::new(code)], &Topology::Chain(2)).err(),
+            Some(TopologyError::WrongProcessorCount {
+                expected: 2,
+                found: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_node_error() {
+        let procs = vec![
+            Processor::new(vec![104, 1, 99]),
+            Processor::new(vec![3, 0, 42]),
+        ];
+        assert_eq!(
+            Network::with_processors(procs, &Topology::Chain(2))
+                .unwrap()
+                .run(),
+            Err(NetworkError::Node {
+                node: 1,
+                error: IntcodeError::UnknownOpcode { pc: 2, opcode: 42 }
+            })
+        );
+    }
+
+    #[test]
+    fn test_deadlock_diagnostic() {
+        let mut network = Network::new(&amplifier(), &Topology::Ring(2)).unwrap();
+        network.feed(0, vec![1, 0]).unwrap();
+        network.feed(1, vec![2]).unwrap();
+        let outcome = network.run().unwrap();
+        assert_eq!(
+            outcome.state.to_string(),
+            "Deadlock: nodes [1] are waiting for input that will never arrive
+  node 0: halted at pc 20, pending input []
+  node 1: waiting at pc 2, pending input []
+"
+        );
+    }
+
+    #[test]
+    fn test_ping_pong_livelock() {
+        let echo = vec![3, 7, 4, 7, 1105, 1, 0, 0];
+        let mut network = Network::new(&echo, &Topology::Ring(2)).unwrap();
+        network.feed(0, vec![5]).unwrap();
+        let outcome = network.run().unwrap();
+        match outcome.state {
+            NetworkState::Livelocked { period, nodes } => {
+                assert_eq!(period, 1);
+                assert_eq!(nodes.len(), 2);
+            }
+            other => panic!("Unexpected state {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_spin_livelock() {
+        let mut network = Network::new(&[1105, 1, 0], &Topology::Chain(1)).unwrap();
+        assert_eq!(
+            network.run().unwrap().state,
+            NetworkState::Livelocked {
+                period: 1,
+                nodes: vec![NodeStatus {
+                    node: 0,
+                    pc: 0,
+                    state: None,
+                    pending: vec![]
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn test_long_loop_is_not_livelock() {
+        let countdown = assemble(
+            "
+            loop:
+                add [count], #-1, [count]
+                jnz [count], #loop
+                output #1
+                halt
+            count: db 5000
+            ",
+        )
+        .unwrap();
+        let outcome = Network::new(&countdown, &Topology::Chain(1))
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(outcome.state, NetworkState::Halted);
+        assert_eq!(outcome.result(), Some(&1));
+    }
+}
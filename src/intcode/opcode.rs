@@ -0,0 +1,93 @@
+/// The instructions understood by the Intcode processor
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Opcode {
+    Add,
+    Mul,
+    Read,
+    Output,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Rel,
+    Halt,
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Mul,
+        Opcode::Read,
+        Opcode::Output,
+        Opcode::Jnz,
+        Opcode::Jz,
+        Opcode::Lt,
+        Opcode::Eq,
+        Opcode::Rel,
+        Opcode::Halt,
+    ];
+
+    /// The most parameters taken by any instruction
+    pub const MAX_PARAMS: usize = 3;
+
+    /// Look up the opcode for the lowest two digits of a raw instruction
+    pub fn from_code(code: i64) -> Option<Opcode> {
+        Opcode::ALL.iter().find(|op| op.code() == code).copied()
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+        Opcode::ALL
+            .iter()
+            .find(|op| op.mnemonic() == mnemonic)
+            .copied()
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Opcode::Add => 1,
+            Opcode::Mul => 2,
+            Opcode::Read => 3,
+            Opcode::Output => 4,
+            Opcode::Jnz => 5,
+            Opcode::Jz => 6,
+            Opcode::Lt => 7,
+            Opcode::Eq => 8,
+            Opcode::Rel => 9,
+            Opcode::Halt => 99,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Add => "add",
+            Opcode::Mul => "mul",
+            Opcode::Read => "read",
+            Opcode::Output => "output",
+            Opcode::Jnz => "jnz",
+            Opcode::Jz => "jz",
+            Opcode::Lt => "lt",
+            Opcode::Eq => "eq",
+            Opcode::Rel => "rel",
+            Opcode::Halt => "halt",
+        }
+    }
+
+    /// The number of parameters that follow the instruction in memory
+    pub fn num_params(self) -> usize {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => 3,
+            Opcode::Jnz | Opcode::Jz => 2,
+            Opcode::Read | Opcode::Output | Opcode::Rel => 1,
+            Opcode::Halt => 0,
+        }
+    }
+
+    /// The index of the parameter this instruction writes its result to, if any
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => Some(2),
+            Opcode::Read => Some(0),
+            _ => None,
+        }
+    }
+}
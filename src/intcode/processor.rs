@@ -0,0 +1,1317 @@
+use std::collections::VecDeque;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::decode::{DecodeCache, Decoded, Param};
+use super::{Cell, Input, IntcodeError, Memory, NoObserver, Observer, Opcode, Output, Undo};
+
+/// How many instructions `run` executes between checks of the clock against its time limit
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+/// An Intcode virtual machine, suspendable at input instructions so that it can be driven
+/// incrementally by a caller
+///
+/// The processor is generic over the type held in each memory cell, `i64` by default. Whatever
+/// the type, arithmetic that overflows it stops execution with `IntcodeError::Overflow`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Processor<C: Cell = i64> {
+    memory: Memory<C>,
+    pc: usize,
+    rel: i64,
+    cache: DecodeCache<C>,
+    /// The most instructions a single call to `run` may execute
+    instruction_budget: Option<u64>,
+    /// The longest a single call to `run` may take
+    time_limit: Option<Duration>,
+}
+
+impl Processor {
+    pub fn new(code: Vec<i64>) -> Processor {
+        Processor::with_memory(Memory::new(&code))
+    }
+
+    /// Create a processor whose memory may grow to at most `max_pages` pages of `PAGE_SIZE`
+    /// cells, beyond which writes fail with `IntcodeError::OutOfMemory`
+    pub fn with_memory_limit(code: Vec<i64>, max_pages: usize) -> Processor {
+        Processor::with_memory(Memory::with_limit(&code, max_pages))
+    }
+
+    pub(crate) fn parse_opcode(
+        pc: usize,
+        raw_opcode: i64,
+    ) -> Result<(i64, (ParamMode, ParamMode, ParamMode)), IntcodeError> {
+        Ok((
+            raw_opcode % 100,
+            (
+                Processor::parse_mode(pc, raw_opcode, 100)?,
+                Processor::parse_mode(pc, raw_opcode, 1_000)?,
+                Processor::parse_mode(pc, raw_opcode, 10_000)?,
+            ),
+        ))
+    }
+
+    fn parse_mode(pc: usize, raw_opcode: i64, offset: i64) -> Result<ParamMode, IntcodeError> {
+        let raw_mode = raw_opcode % (offset * 10) / offset;
+        match raw_mode {
+            0 => Ok(ParamMode::Position),
+            1 => Ok(ParamMode::Immediate),
+            2 => Ok(ParamMode::Relative),
+            _ => Err(IntcodeError::UnknownParamMode {
+                pc,
+                opcode: raw_opcode,
+                param: (offset / 100) as usize,
+                mode: raw_mode,
+            }),
+        }
+    }
+}
+
+impl<C: Cell> Processor<C> {
+    /// Create a processor that starts executing `memory` at address 0, which is how to get one
+    /// with a cell type other than `i64`
+    pub fn with_memory(memory: Memory<C>) -> Processor<C> {
+        Processor::with_state(memory, 0, 0)
+    }
+
+    /// Create a processor that starts executing `code` at address 0 with cells of type `C`,
+    /// failing with `IntcodeError::Overflow` if any value in it does not fit in a cell
+    pub fn from_code(code: &[i64]) -> Result<Processor<C>, IntcodeError> {
+        let cells = code
+            .iter()
+            .map(|value| C::from_i64(*value).ok_or(IntcodeError::Overflow { pc: 0 }))
+            .collect::<Result<Vec<C>, IntcodeError>>()?;
+        Ok(Processor::with_memory(Memory::new(&cells)))
+    }
+
+    /// Create a processor that resumes executing `memory` at `pc` with relative base `rel`
+    pub fn with_state(memory: Memory<C>, pc: usize, rel: i64) -> Processor<C> {
+        Processor {
+            cache: DecodeCache::new(memory.extent()),
+            memory,
+            pc,
+            rel,
+            instruction_budget: None,
+            time_limit: None,
+        }
+    }
+
+    /// The current contents of the processor's memory
+    pub fn memory(&self) -> &Memory<C> {
+        &self.memory
+    }
+
+    /// The address of the next instruction to be executed
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// The current relative base used by relative mode parameters
+    pub fn rel(&self) -> i64 {
+        self.rel
+    }
+
+    pub(crate) fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
+    }
+
+    pub(crate) fn set_rel(&mut self, rel: i64) {
+        self.rel = rel;
+    }
+
+    /// Write `value` to `addr` as the instruction at the program counter would
+    pub(crate) fn write(&mut self, addr: usize, value: C) -> Result<(), IntcodeError> {
+        self.store(addr, value, &mut NoObserver)
+    }
+
+    /// Check that any value could be written to `addr`, as an input instruction does before it
+    /// reads so that a value is never consumed only to be lost
+    pub(crate) fn check_writable(&self, addr: usize) -> Result<(), IntcodeError> {
+        if self.memory.writable(addr) {
+            Ok(())
+        } else {
+            Err(IntcodeError::OutOfMemory {
+                pc: self.pc,
+                addr,
+                max_pages: self.memory.max_pages(),
+            })
+        }
+    }
+
+    /// Turn the cache of decoded instructions on or off. It is on by default, and only worth
+    /// turning off to compare against an interpreter that decodes every instruction as it runs.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.cache.set_enabled(enabled);
+    }
+
+    /// Limit each call to `run`, `execute` or their observed forms to at most `budget`
+    /// instructions, after which it suspends with `ProcessorState::BudgetExhausted` and can be
+    /// resumed by calling it again. `None`, the default, lets the program run for as long as it
+    /// likes.
+    pub fn set_instruction_budget(&mut self, budget: Option<u64>) {
+        self.instruction_budget = budget;
+    }
+
+    /// Limit each call to `run`, `execute` or their observed forms to roughly `limit` of wall
+    /// clock time, after which it suspends as it does when its instruction budget runs out.
+    /// The clock is only checked every so many instructions, so the limit may be overrun
+    /// slightly.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.time_limit = limit;
+    }
+
+    /// Run the program until it halts, blocks waiting on input or runs out of budget, returning
+    /// the state it stopped in along with everything it output along the way
+    pub fn execute(
+        &mut self,
+        input: VecDeque<C>,
+    ) -> Result<(ProcessorState, VecDeque<C>), IntcodeError> {
+        self.execute_observed(input, &mut NoObserver)
+    }
+
+    /// Run the program as `execute` does, reporting everything it does to `observer`
+    pub fn execute_observed<O: Observer<C>>(
+        &mut self,
+        mut input: VecDeque<C>,
+        observer: &mut O,
+    ) -> Result<(ProcessorState, VecDeque<C>), IntcodeError> {
+        let mut output: VecDeque<C> = VecDeque::new();
+        let state = self.run_observed(&mut input, &mut output, observer)?;
+        Ok((state, output))
+    }
+
+    /// Run the program until it halts, `input` has nothing more to give, or it uses up its
+    /// instruction budget or time limit, reading from `input` and writing to `output` as it goes
+    pub fn run<I: Input<C>, S: Output<C>>(
+        &mut self,
+        input: &mut I,
+        output: &mut S,
+    ) -> Result<ProcessorState, IntcodeError> {
+        self.run_observed(input, output, &mut NoObserver)
+    }
+
+    /// Run the program as `run` does, reporting everything it does to `observer`
+    pub fn run_observed<I: Input<C>, S: Output<C>, O: Observer<C>>(
+        &mut self,
+        input: &mut I,
+        output: &mut S,
+        observer: &mut O,
+    ) -> Result<ProcessorState, IntcodeError> {
+        if self.instruction_budget.is_none() && self.time_limit.is_none() {
+            loop {
+                if let Some(state) = self.step_observed(input, output, observer)? {
+                    return Ok(state);
+                }
+            }
+        }
+
+        let budget = self.instruction_budget.unwrap_or(u64::MAX);
+        let deadline = self.time_limit.map(|limit| Instant::now() + limit);
+        let mut executed = 0;
+        loop {
+            let out_of_time = executed % CLOCK_CHECK_INTERVAL == 0
+                && deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            if executed >= budget || out_of_time {
+                return Ok(ProcessorState::BudgetExhausted);
+            }
+            if let Some(state) = self.step_observed(input, output, observer)? {
+                return Ok(state);
+            }
+            executed += 1;
+        }
+    }
+
+    /// Run the program on its own thread, returning a handle that yields the state it stopped in.
+    ///
+    /// With channels for `input` and `output` this lets machines be wired together directly;
+    /// the thread finishes when the program halts, every sender feeding its input is dropped, or
+    /// it runs out of budget.
+    pub fn spawn<I, S>(
+        mut self,
+        mut input: I,
+        mut output: S,
+    ) -> JoinHandle<Result<ProcessorState, IntcodeError>>
+    where
+        C: Send + 'static,
+        I: Input<C> + Send + 'static,
+        S: Output<C> + Send + 'static,
+    {
+        thread::spawn(move || self.run(&mut input, &mut output))
+    }
+
+    /// Execute a single instruction, consuming from `input` and appending to `output` as needed.
+    ///
+    /// Returns the state the processor stopped in if the instruction was a halt or an input with
+    /// no data available, in which case the program counter is left pointing at it.
+    pub fn step<I: Input<C>, S: Output<C>>(
+        &mut self,
+        input: &mut I,
+        output: &mut S,
+    ) -> Result<Option<ProcessorState>, IntcodeError> {
+        self.step_observed(input, output, &mut NoObserver)
+    }
+
+    /// Execute a single instruction as `step` does, appending a record of what it changed to
+    /// `log` so that it can later be reversed with `undo`. Nothing is recorded for a halt, an
+    /// input that blocks, or an instruction that fails, since none of those change anything. In
+    /// particular an input instruction checks its destination before reading, so one that fails
+    /// leaves its value in `input`.
+    pub fn step_undoable<I: Input<C>, S: Output<C>>(
+        &mut self,
+        input: &mut I,
+        output: &mut S,
+        log: &mut Vec<Undo<C>>,
+    ) -> Result<Option<ProcessorState>, IntcodeError> {
+        let mut undo = Undo::new(self.pc, self.rel, self.memory.extent());
+        let state = self.step_observed(input, output, &mut undo)?;
+        if state.is_none() {
+            log.push(undo);
+        }
+        Ok(state)
+    }
+
+    /// Reverse an instruction recorded by `step_undoable`, restoring the cells it wrote, the
+    /// extent of memory, and the program counter and relative base from before it ran.
+    /// Instructions must be undone in the reverse of the order they were executed.
+    pub fn undo(&mut self, undo: &Undo<C>) {
+        for (addr, old) in undo.writes.iter().rev() {
+            self.memory.set(*addr, old.clone());
+            self.cache.invalidate(*addr);
+        }
+        self.memory.restore_extent(undo.extent);
+        self.pc = undo.pc;
+        self.rel = undo.rel;
+    }
+
+    /// Execute a single instruction as `step` does, reporting everything it does to `observer`.
+    ///
+    /// An input instruction that blocks for lack of data is not reported, since it does not
+    /// execute until it is resumed with data available.
+    pub fn step_observed<I: Input<C>, S: Output<C>, O: Observer<C>>(
+        &mut self,
+        input: &mut I,
+        output: &mut S,
+        observer: &mut O,
+    ) -> Result<Option<ProcessorState>, IntcodeError> {
+        let Decoded { opcode, params } = match self.cache.get(self.pc) {
+            Some(decoded) => decoded.clone(),
+            None => {
+                let decoded = self.decode()?;
+                self.cache.insert(self.pc, &decoded);
+                decoded
+            }
+        };
+
+        // An input instruction checks where it will write before taking a value, so that it
+        // cannot fail once the value is consumed
+        let input_val = match opcode {
+            Opcode::Read => {
+                let write_addr = self.parse_inst_addr(&params)?;
+                self.check_writable(write_addr)?;
+                match input.read() {
+                    Some(value) => Some((write_addr, value)),
+                    None => return Ok(Some(ProcessorState::IoWait)),
+                }
+            }
+            _ => None,
+        };
+        observer.instruction(self.pc, opcode);
+
+        let stop_state = match opcode {
+            Opcode::Halt => Some(ProcessorState::Halted),
+            Opcode::Add => {
+                // add in1 in2 out_addr
+                let (arg1, arg2, out_addr) = self.parse_inst_data_data_addr(&params, observer)?;
+                let sum = self.arith(arg1.checked_add(&arg2))?;
+                self.store(out_addr, sum, observer)?;
+
+                self.pc += 4;
+                None
+            }
+            Opcode::Mul => {
+                // mul in1 in2 out_addr
+                let (arg1, arg2, out_addr) = self.parse_inst_data_data_addr(&params, observer)?;
+                let product = self.arith(arg1.checked_mul(&arg2))?;
+                self.store(out_addr, product, observer)?;
+
+                self.pc += 4;
+                None
+            }
+            Opcode::Read => {
+                // input write_addr
+                let (write_addr, input_val) = input_val.unwrap();
+                observer.input(&input_val);
+                self.store(write_addr, input_val, observer)?;
+
+                self.pc += 2;
+                None
+            }
+            Opcode::Output => {
+                // output read_addr
+                let value = self.parse_inst_data(&params, observer)?;
+                observer.output(&value);
+                output.write(value);
+
+                self.pc += 2;
+                None
+            }
+            Opcode::Jnz => {
+                // jump-if-true cond addr
+                let (cond, addr) = self.parse_inst_data_data(&params, observer)?;
+                if !cond.is_zero() {
+                    self.pc = self.jump_target(&addr)?;
+                } else {
+                    self.pc += 3;
+                }
+                None
+            }
+            Opcode::Jz => {
+                // jump-if-false cond addr
+                let (cond, addr) = self.parse_inst_data_data(&params, observer)?;
+                if cond.is_zero() {
+                    self.pc = self.jump_target(&addr)?;
+                } else {
+                    self.pc += 3;
+                }
+                None
+            }
+            Opcode::Lt => {
+                // less-than val1 val2 out_addr
+                let (arg1, arg2, out_addr) = self.parse_inst_data_data_addr(&params, observer)?;
+                self.store(
+                    out_addr,
+                    if arg1 < arg2 { C::one() } else { C::zero() },
+                    observer,
+                )?;
+
+                self.pc += 4;
+                None
+            }
+            Opcode::Eq => {
+                // equals val1 val2 out_addr
+                let (arg1, arg2, out_addr) = self.parse_inst_data_data_addr(&params, observer)?;
+                self.store(
+                    out_addr,
+                    if arg1 == arg2 { C::one() } else { C::zero() },
+                    observer,
+                )?;
+
+                self.pc += 4;
+                None
+            }
+            Opcode::Rel => {
+                // rel val
+                let arg1 = self.parse_inst_data(&params, observer)?;
+                let offset = self.arith(arg1.to_i64())?;
+                self.rel = self.arith(self.rel.checked_add(offset))?;
+
+                self.pc += 2;
+                None
+            }
+        };
+
+        Ok(stop_state)
+    }
+
+    /// Decode the instruction at the program counter
+    fn decode(&self) -> Result<Decoded<C>, IntcodeError> {
+        let raw_opcode = self.to_i64_saturating(&self.memory.get(self.pc));
+        let (opcode, (param1_mode, param2_mode, param3_mode)) =
+            Processor::parse_opcode(self.pc, raw_opcode)?;
+        let opcode = Opcode::from_code(opcode).ok_or(IntcodeError::UnknownOpcode {
+            pc: self.pc,
+            opcode: raw_opcode,
+        })?;
+
+        let mut params = [Param::Unused, Param::Unused, Param::Unused];
+        let modes = [param1_mode, param2_mode, param3_mode];
+        for (i, (param, mode)) in params
+            .iter_mut()
+            .zip(modes.iter())
+            .take(opcode.num_params())
+            .enumerate()
+        {
+            *param = Param::decode(*mode, self.memory.get(self.pc + i + 1));
+        }
+        Ok(Decoded { opcode, params })
+    }
+
+    fn parse_inst_addr(&self, params: &[Param<C>; 3]) -> Result<usize, IntcodeError> {
+        self.get_addr(1, &params[0])
+    }
+
+    fn parse_inst_data<O: Observer<C>>(
+        &self,
+        params: &[Param<C>; 3],
+        observer: &mut O,
+    ) -> Result<C, IntcodeError> {
+        let param1_addr = self.get_addr(1, &params[0])?;
+        Ok(self.load_param(&params[0], param1_addr, observer))
+    }
+
+    fn parse_inst_data_data<O: Observer<C>>(
+        &self,
+        params: &[Param<C>; 3],
+        observer: &mut O,
+    ) -> Result<(C, C), IntcodeError> {
+        let param1_addr = self.get_addr(1, &params[0])?;
+        let param2_addr = self.get_addr(2, &params[1])?;
+        Ok((
+            self.load_param(&params[0], param1_addr, observer),
+            self.load_param(&params[1], param2_addr, observer),
+        ))
+    }
+
+    fn parse_inst_data_data_addr<O: Observer<C>>(
+        &self,
+        params: &[Param<C>; 3],
+        observer: &mut O,
+    ) -> Result<(C, C, usize), IntcodeError> {
+        let param1_addr = self.get_addr(1, &params[0])?;
+        let param2_addr = self.get_addr(2, &params[1])?;
+        let param3_addr = self.get_addr(3, &params[2])?;
+        Ok((
+            self.load_param(&params[0], param1_addr, observer),
+            self.load_param(&params[1], param2_addr, observer),
+            param3_addr,
+        ))
+    }
+
+    /// Load the value of a parameter from `addr`, or straight from the decoded instruction if it
+    /// is in immediate mode
+    fn load_param<O: Observer<C>>(&self, param: &Param<C>, addr: usize, observer: &mut O) -> C {
+        match param {
+            Param::Immediate(value) => {
+                observer.memory_read(addr, value);
+                value.clone()
+            }
+            _ => self.load(addr, observer),
+        }
+    }
+
+    fn load<O: Observer<C>>(&self, addr: usize, observer: &mut O) -> C {
+        let value = self.memory.get(addr);
+        observer.memory_read(addr, &value);
+        value
+    }
+
+    fn store<O: Observer<C>>(
+        &mut self,
+        addr: usize,
+        value: C,
+        observer: &mut O,
+    ) -> Result<(), IntcodeError> {
+        let old = self.memory.get(addr);
+        if !self.memory.set(addr, value.clone()) {
+            return Err(IntcodeError::OutOfMemory {
+                pc: self.pc,
+                addr,
+                max_pages: self.memory.max_pages(),
+            });
+        }
+        self.cache.invalidate(addr);
+        observer.memory_write(addr, &old, &value);
+        Ok(())
+    }
+
+    fn get_addr(&self, offset: usize, param: &Param<C>) -> Result<usize, IntcodeError> {
+        match param {
+            Param::Immediate(_) => Ok(self.pc + offset),
+            Param::Position(addr) => Ok(*addr),
+            Param::Relative(param) => {
+                let addr = self.arith(self.rel.checked_add(*param))?;
+                self.address(addr)
+            }
+            Param::Invalid(ParamMode::Position, param) => self.jump_target(param),
+            Param::Invalid(_, _) => Err(IntcodeError::Overflow { pc: self.pc }),
+            Param::Unused => panic!("Parameter {} is not used at {}", offset, self.pc),
+        }
+    }
+
+    fn jump_target(&self, addr: &C) -> Result<usize, IntcodeError> {
+        let addr = self.arith(addr.to_i64())?;
+        self.address(addr)
+    }
+
+    fn address(&self, addr: i64) -> Result<usize, IntcodeError> {
+        if addr < 0 {
+            Err(IntcodeError::NegativeAddress { pc: self.pc, addr })
+        } else {
+            Ok(addr as usize)
+        }
+    }
+
+    /// Unwrap the result of a checked calculation, treating `None` as an overflow
+    fn arith<T>(&self, result: Option<T>) -> Result<T, IntcodeError> {
+        result.ok_or(IntcodeError::Overflow { pc: self.pc })
+    }
+
+    /// Convert a cell to an `i64` for decoding as an opcode, clamping values that do not fit
+    fn to_i64_saturating(&self, value: &C) -> i64 {
+        value.to_i64().unwrap_or(if *value < C::zero() {
+            i64::MIN
+        } else {
+            i64::MAX
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ParamMode {
+    Immediate,
+    Position,
+    Relative,
+}
+
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub enum ProcessorState {
+    /// The processor is currently waiting at an input instruction for additional data to arrive
+    /// through the input deque
+    IoWait,
+    /// The processor encountered a HALT instruction and has stopped running
+    Halted,
+    /// The processor used up its instruction budget or time limit for a single run, and will
+    /// carry on from where it left off when run again
+    BudgetExhausted,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num::BigInt;
+
+    fn run_to_memory(code: Vec<i64>, input: VecDeque<i64>) -> Vec<i64> {
+        let mut proc = Processor::new(code);
+        let (state, _) = proc.execute(input).unwrap();
+        assert_eq!(state, ProcessorState::Halted);
+        proc.memory().to_vec()
+    }
+
+    #[test]
+    fn test_memory_1() {
+        assert_eq!(
+            run_to_memory(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], deque!()),
+            vec![3500, 9, 10, 70, 2, 3, 11, 0, 99, 30, 40, 50]
+        );
+    }
+
+    #[test]
+    fn test_memory_2() {
+        assert_eq!(
+            run_to_memory(vec![1, 0, 0, 0, 99], deque!()),
+            vec![2, 0, 0, 0, 99]
+        );
+    }
+
+    #[test]
+    fn test_memory_3() {
+        assert_eq!(
+            run_to_memory(vec![2, 3, 0, 3, 99], deque!()),
+            vec![2, 3, 0, 6, 99]
+        );
+    }
+
+    #[test]
+    fn test_memory_4() {
+        assert_eq!(
+            run_to_memory(vec![2, 4, 4, 5, 99, 0], deque!()),
+            vec![2, 4, 4, 5, 99, 9801]
+        );
+    }
+
+    #[test]
+    fn test_memory_5() {
+        assert_eq!(
+            run_to_memory(vec![1, 1, 1, 4, 99, 5, 6, 0, 99], deque!()),
+            vec![30, 1, 1, 4, 2, 5, 6, 0, 99]
+        );
+    }
+
+    #[test]
+    fn test_input() {
+        assert_eq!(run_to_memory(vec![3, 1, 99], deque!(42)), vec![3, 42, 99])
+    }
+
+    #[test]
+    fn test_output() {
+        assert_eq!(
+            Processor::new(vec![4, 3, 99, 42])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(42))
+        )
+    }
+
+    #[test]
+    fn test_input_output() {
+        assert_eq!(
+            Processor::new(vec![3, 1, 4, 1, 99])
+                .execute(deque!(42))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(42))
+        )
+    }
+
+    #[test]
+    fn test_no_input_suspend() {
+        assert_eq!(
+            Processor::new(vec![3, 1, 4, 1, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::IoWait, deque!())
+        )
+    }
+
+    #[test]
+    fn test_resume_after_suspend() {
+        let mut proc = Processor::new(vec![3, 1, 4, 1, 99]);
+        assert_eq!(
+            proc.execute(deque!()).unwrap(),
+            (ProcessorState::IoWait, deque!())
+        );
+        assert_eq!(
+            proc.execute(deque!(42)).unwrap(),
+            (ProcessorState::Halted, deque!(42))
+        )
+    }
+
+    #[test]
+    fn test_add_pos_pos() {
+        assert_eq!(
+            Processor::new(vec![1, 7, 8, 9, 4, 9, 99, 4, 5, 0])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(9))
+        )
+    }
+
+    #[test]
+    fn test_add_imm_pos() {
+        assert_eq!(
+            Processor::new(vec![101, 1, 8, 9, 4, 9, 99, 4, 5, 0])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(6))
+        )
+    }
+
+    #[test]
+    fn test_add_pos_imm() {
+        assert_eq!(
+            Processor::new(vec![1001, 7, 1, 9, 4, 9, 99, 4, 5, 0])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(5))
+        )
+    }
+
+    #[test]
+    fn test_mul_pos_pos() {
+        assert_eq!(
+            Processor::new(vec![2, 7, 8, 9, 4, 9, 99, 4, 5, 0])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(20))
+        )
+    }
+
+    #[test]
+    fn test_mul_imm_pos() {
+        assert_eq!(
+            Processor::new(vec![102, 1, 8, 9, 4, 9, 99, 4, 5, 0])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(5))
+        )
+    }
+
+    #[test]
+    fn test_mul_pos_imm() {
+        assert_eq!(
+            Processor::new(vec![1002, 7, 1, 9, 4, 9, 99, 4, 5, 0])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(4))
+        )
+    }
+
+    #[test]
+    fn test_equal_pos_1() {
+        assert_eq!(
+            Processor::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8])
+                .execute(deque!(8))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_equal_pos_0() {
+        assert_eq!(
+            Processor::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8])
+                .execute(deque!(7))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(0))
+        )
+    }
+
+    #[test]
+    fn test_lt_pos_1() {
+        assert_eq!(
+            Processor::new(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8])
+                .execute(deque!(7))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_lt_pos_0() {
+        assert_eq!(
+            Processor::new(vec![3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8])
+                .execute(deque!(10))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(0))
+        )
+    }
+
+    #[test]
+    fn test_equal_imm_1() {
+        assert_eq!(
+            Processor::new(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99])
+                .execute(deque!(8))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_equal_imm_0() {
+        assert_eq!(
+            Processor::new(vec![3, 3, 1108, -1, 8, 3, 4, 3, 99])
+                .execute(deque!(7))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(0))
+        )
+    }
+
+    #[test]
+    fn test_lt_imm_1() {
+        assert_eq!(
+            Processor::new(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99])
+                .execute(deque!(7))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_lt_imm_0() {
+        assert_eq!(
+            Processor::new(vec![3, 3, 1107, -1, 8, 3, 4, 3, 99])
+                .execute(deque!(10))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(0))
+        )
+    }
+
+    #[test]
+    fn test_jump_nz_pos_1() {
+        assert_eq!(
+            Processor::new(vec![
+                3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9
+            ])
+            .execute(deque!(10))
+            .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_jump_nz_pos_0() {
+        assert_eq!(
+            Processor::new(vec![
+                3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9
+            ])
+            .execute(deque!(0))
+            .unwrap(),
+            (ProcessorState::Halted, deque!(0))
+        )
+    }
+
+    #[test]
+    fn test_jump_nz_imm_1() {
+        assert_eq!(
+            Processor::new(vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1])
+                .execute(deque!(10))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_jump_nz_imm_0() {
+        assert_eq!(
+            Processor::new(vec![3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1])
+                .execute(deque!(0))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(0))
+        )
+    }
+
+    #[test]
+    fn test_all_lt_8() {
+        assert_eq!(
+            Processor::new(vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ])
+            .execute(deque!(7))
+            .unwrap(),
+            (ProcessorState::Halted, deque!(999))
+        )
+    }
+
+    #[test]
+    fn test_all_eq_8() {
+        assert_eq!(
+            Processor::new(vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ])
+            .execute(deque!(8))
+            .unwrap(),
+            (ProcessorState::Halted, deque!(1000))
+        )
+    }
+
+    #[test]
+    fn test_all_gt_8() {
+        assert_eq!(
+            Processor::new(vec![
+                3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36,
+                98, 0, 0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000,
+                1, 20, 4, 20, 1105, 1, 46, 98, 99,
+            ])
+            .execute(deque!(9))
+            .unwrap(),
+            (ProcessorState::Halted, deque!(1001))
+        )
+    }
+
+    #[test]
+    fn test_quine() {
+        assert_eq!(
+            Processor::new(vec![
+                109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99
+            ])
+            .execute(deque!())
+            .unwrap(),
+            (
+                ProcessorState::Halted,
+                deque!(109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99)
+            )
+        )
+    }
+
+    #[test]
+    fn test_large_number() {
+        assert_eq!(
+            Processor::new(vec![104, 1125899906842624, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1125899906842624))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_1() {
+        assert_eq!(
+            Processor::new(vec![109, -1, 4, 1, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(-1))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_2() {
+        assert_eq!(
+            Processor::new(vec![109, -1, 104, 1, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_3() {
+        assert_eq!(
+            Processor::new(vec![109, -1, 204, 1, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(109))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_4() {
+        assert_eq!(
+            Processor::new(vec![109, 1, 9, 2, 204, -6, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(204))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_5() {
+        assert_eq!(
+            Processor::new(vec![109, 1, 109, 9, 204, -6, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(204))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_6() {
+        assert_eq!(
+            Processor::new(vec![109, 1, 209, -1, 204, -106, 99])
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(204))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_7() {
+        assert_eq!(
+            Processor::new(vec![109, 1, 3, 3, 204, 2, 99])
+                .execute(deque!(42))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(42))
+        )
+    }
+
+    #[test]
+    fn test_203_micro_8() {
+        assert_eq!(
+            Processor::new(vec![109, 1, 203, 2, 204, 2, 99])
+                .execute(deque!(42))
+                .unwrap(),
+            (ProcessorState::Halted, deque!(42))
+        )
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        assert_eq!(
+            Processor::new(vec![1101, 1, 1, 5, 42, 0]).execute(deque!()),
+            Err(IntcodeError::UnknownOpcode { pc: 4, opcode: 42 })
+        )
+    }
+
+    #[test]
+    fn test_unknown_param_mode() {
+        assert_eq!(
+            Processor::new(vec![304, 1, 99]).execute(deque!()),
+            Err(IntcodeError::UnknownParamMode {
+                pc: 0,
+                opcode: 304,
+                param: 1,
+                mode: 3
+            })
+        )
+    }
+
+    #[test]
+    fn test_negative_relative_address() {
+        assert_eq!(
+            Processor::new(vec![109, -5, 204, 1, 99]).execute(deque!()),
+            Err(IntcodeError::NegativeAddress { pc: 2, addr: -4 })
+        )
+    }
+
+    #[test]
+    fn test_negative_position_address() {
+        assert_eq!(
+            Processor::new(vec![4, -1, 99]).execute(deque!()),
+            Err(IntcodeError::NegativeAddress { pc: 0, addr: -1 })
+        )
+    }
+
+    #[test]
+    fn test_negative_jump_target() {
+        assert_eq!(
+            Processor::new(vec![1105, 1, -3, 99]).execute(deque!()),
+            Err(IntcodeError::NegativeAddress { pc: 0, addr: -3 })
+        )
+    }
+
+    #[test]
+    fn test_run_off_end_of_memory() {
+        assert_eq!(
+            Processor::new(vec![1101, 1, 1, 0]).execute(deque!()),
+            Err(IntcodeError::UnknownOpcode { pc: 4, opcode: 0 })
+        )
+    }
+
+    #[test]
+    fn test_huge_address() {
+        let mut proc = Processor::new(vec![
+            1101,
+            3,
+            4,
+            1_000_000_000_000,
+            4,
+            1_000_000_000_000,
+            99,
+        ]);
+        assert_eq!(
+            proc.execute(deque!()).unwrap(),
+            (ProcessorState::Halted, deque!(7))
+        );
+        assert_eq!(proc.memory().pages(), 2);
+    }
+
+    #[test]
+    fn test_out_of_memory() {
+        assert_eq!(
+            Processor::with_memory_limit(vec![1101, 1, 1, 5000, 1101, 1, 1, 10_000, 99], 2)
+                .execute(deque!()),
+            Err(IntcodeError::OutOfMemory {
+                pc: 4,
+                addr: 10_000,
+                max_pages: 2
+            })
+        )
+    }
+
+    #[test]
+    fn test_failed_read_keeps_input() {
+        let mut input = deque!(7);
+        let mut output = deque!();
+
+        // Reading into a negative relative address
+        let mut proc = Processor::new(vec![109, -5, 203, 0, 99]);
+        assert_eq!(
+            proc.run(&mut input, &mut output),
+            Err(IntcodeError::NegativeAddress { pc: 2, addr: -5 })
+        );
+        assert_eq!(input, deque!(7));
+
+        // Reading into a page beyond the memory limit, even though 0 would fit
+        let mut proc = Processor::with_memory_limit(vec![3, 5000, 99], 1);
+        let mut zero = deque!(0);
+        assert_eq!(
+            proc.run(&mut zero, &mut output),
+            Err(IntcodeError::OutOfMemory {
+                pc: 0,
+                addr: 5000,
+                max_pages: 1
+            })
+        );
+        assert_eq!(zero, deque!(0));
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(
+            Processor::new(vec![1102, 1 << 62, 4, 5, 99, 0]).execute(deque!()),
+            Err(IntcodeError::Overflow { pc: 0 })
+        )
+    }
+
+    #[test]
+    fn test_relative_base_overflow() {
+        assert_eq!(
+            Processor::new(vec![109, i64::MAX, 109, 1, 99]).execute(deque!()),
+            Err(IntcodeError::Overflow { pc: 2 })
+        )
+    }
+
+    #[test]
+    fn test_i32_cells() {
+        let code = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        assert_eq!(
+            Processor::<i32>::with_memory(Memory::new(&[1102, 46340, 46340, 7, 4, 7, 99, 0]))
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(2147395600))
+        );
+        assert_eq!(
+            Processor::<i32>::with_memory(Memory::new(&code)).execute(deque!()),
+            Err(IntcodeError::Overflow { pc: 0 })
+        );
+        assert_eq!(
+            Processor::new(code.iter().map(|&v| v as i64).collect())
+                .execute(deque!())
+                .unwrap(),
+            (ProcessorState::Halted, deque!(1219070632396864))
+        );
+    }
+
+    #[test]
+    fn test_code_out_of_range() {
+        assert_eq!(
+            Processor::<i32>::from_code(&[104, i64::MAX, 99]),
+            Err(IntcodeError::Overflow { pc: 0 })
+        );
+        assert_eq!(
+            Processor::<i32>::from_code(&[104, i32::MAX as i64, 99])
+                .unwrap()
+                .execute(deque!()),
+            Ok((ProcessorState::Halted, deque!(i32::MAX)))
+        );
+    }
+
+    #[test]
+    fn test_big_int_cells() {
+        let big = "1000000000000000000".parse::<BigInt>().unwrap();
+        let code = vec![2, 11, 11, 11, 1, 11, 11, 11, 4, 11, 99]
+            .into_iter()
+            .map(BigInt::from)
+            .chain(Some(big.clone()))
+            .collect::<Vec<BigInt>>();
+        assert_eq!(
+            Processor::with_memory(Memory::new(&code))
+                .execute(deque!())
+                .unwrap(),
+            (
+                ProcessorState::Halted,
+                deque!(big.clone() * big * BigInt::from(2))
+            )
+        );
+    }
+
+    #[test]
+    fn test_self_modifying_code_with_cache() {
+        // Outputs #1, then rewrites that immediate to 7 and loops back once to output it again
+        let code = vec![
+            104, 1, 1101, 0, 7, 1, 1005, 20, 16, 1101, 0, 1, 20, 1105, 1, 0, 99, 0, 0, 0, 0,
+        ];
+        for cached in &[true, false] {
+            let mut proc = Processor::new(code.clone());
+            proc.set_decode_cache(*cached);
+            assert_eq!(
+                proc.execute(deque!()),
+                Ok((ProcessorState::Halted, deque!(1, 7)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_cache_does_not_affect_equality() {
+        let code = vec![1101, 2, 3, 5, 99, 0];
+        let mut cached = Processor::new(code.clone());
+        let mut uncached = Processor::new(code);
+        uncached.set_decode_cache(false);
+        cached.execute(deque!()).unwrap();
+        uncached.execute(deque!()).unwrap();
+        assert_eq!(cached, uncached);
+    }
+
+    #[test]
+    fn test_undo() {
+        // Outputs a copy of itself, using the relative base and memory beyond the program
+        let code = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut proc = Processor::new(code.clone());
+        let mut input = deque!();
+        let mut output = deque!();
+        let mut log = Vec::new();
+        let mut states = vec![proc.clone()];
+        while proc
+            .step_undoable(&mut input, &mut output, &mut log)
+            .unwrap()
+            .is_none()
+        {
+            states.push(proc.clone());
+        }
+        assert_eq!(output, code.iter().cloned().collect::<VecDeque<_>>());
+        assert_eq!(log.len(), states.len() - 1);
+
+        while let Some(undo) = log.pop() {
+            proc.undo(&undo);
+            assert_eq!(proc, states[log.len()]);
+        }
+        assert_eq!(proc, Processor::new(code));
+    }
+
+    /// Counts up in cell 7 forever, two instructions per count
+    fn counter() -> Vec<i64> {
+        vec![1001, 7, 1, 7, 1105, 1, 0, 0]
+    }
+
+    #[test]
+    fn test_instruction_budget() {
+        let mut proc = Processor::new(counter());
+        proc.set_instruction_budget(Some(10));
+        assert_eq!(
+            proc.execute(deque!()),
+            Ok((ProcessorState::BudgetExhausted, deque!()))
+        );
+        assert_eq!(proc.memory().get(7), 5);
+
+        // Each run gets a fresh budget, picking up where the last left off
+        assert_eq!(
+            proc.execute(deque!()),
+            Ok((ProcessorState::BudgetExhausted, deque!()))
+        );
+        assert_eq!(proc.memory().get(7), 10);
+
+        // Programs that finish within the budget stop as they normally would
+        let mut proc = Processor::new(vec![3, 5, 4, 5, 99, 0]);
+        proc.set_instruction_budget(Some(3));
+        assert_eq!(
+            proc.execute(deque!()),
+            Ok((ProcessorState::IoWait, deque!()))
+        );
+        assert_eq!(
+            proc.execute(deque!(42)),
+            Ok((ProcessorState::Halted, deque!(42)))
+        );
+    }
+
+    #[test]
+    fn test_time_limit() {
+        // A limit that has already passed stops the program before its first instruction
+        let mut proc = Processor::new(counter());
+        proc.set_time_limit(Some(Duration::ZERO));
+        assert_eq!(
+            proc.execute(deque!()),
+            Ok((ProcessorState::BudgetExhausted, deque!()))
+        );
+        assert_eq!(proc.pc(), 0);
+        assert_eq!(proc.memory().get(7), 0);
+
+        // One that is nowhere near leaves the instruction budget to stop it
+        proc.set_time_limit(Some(Duration::from_secs(3600)));
+        proc.set_instruction_budget(Some(10));
+        assert_eq!(
+            proc.execute(deque!()),
+            Ok((ProcessorState::BudgetExhausted, deque!()))
+        );
+        assert_eq!(proc.memory().get(7), 5);
+    }
+
+    #[test]
+    fn test_undo_failed_read() {
+        let mut proc = Processor::new(vec![109, -5, 203, 0, 99]);
+        let mut input = deque!(7);
+        let mut output = deque!();
+        let mut log = Vec::new();
+        assert_eq!(
+            proc.step_undoable(&mut input, &mut output, &mut log),
+            Ok(None)
+        );
+        assert!(proc
+            .step_undoable(&mut input, &mut output, &mut log)
+            .is_err());
+        assert_eq!(log.len(), 1);
+        assert_eq!(input, deque!(7));
+    }
+}
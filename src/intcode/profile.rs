@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+
+use super::{Disassembly, Line, Observer, Opcode};
+
+/// How many of the hottest addresses to list in a report
+const HOTTEST: usize = 20;
+
+/// Counts what a `Processor` executes, for finding hot spots and unreached code.
+///
+/// Attach it with `execute_observed` or `run_observed`, possibly across several runs, then ask
+/// for a `report` or an `annotate`d disassembly.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    /// How many instructions were executed at each address
+    pub pc_counts: BTreeMap<usize, u64>,
+    /// How many instructions were executed with each opcode
+    pub opcode_counts: BTreeMap<Opcode, u64>,
+    /// How many times each cell was written
+    pub writes: BTreeMap<usize, u64>,
+    /// Every cell that held an executed instruction or one of its parameters
+    pub executed_cells: BTreeSet<usize>,
+    /// The opcode last executed at each address, so that the cells an instruction covers are
+    /// only recorded again when self-modification changes its size
+    last_opcode: HashMap<usize, Opcode>,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// The total number of instructions executed
+    pub fn instructions(&self) -> u64 {
+        self.opcode_counts.values().sum()
+    }
+
+    /// Cells that were both written and executed as part of an instruction, in either order,
+    /// with the number of times each was written
+    pub fn self_modified(&self) -> Vec<(usize, u64)> {
+        self.writes
+            .iter()
+            .filter(|(addr, _)| self.executed_cells.contains(addr))
+            .map(|(addr, count)| (*addr, *count))
+            .collect()
+    }
+
+    /// The instructions in `code` that were never executed. The listing is seeded with every
+    /// address that was executed, so code only reached through dynamic jumps is included.
+    pub fn unreached(&self, code: &[i64]) -> Vec<usize> {
+        self.disassemble(code)
+            .lines
+            .iter()
+            .filter_map(|line| match line {
+                Line::Code(inst) if !self.pc_counts.contains_key(&inst.addr) => Some(inst.addr),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// A summary of the run: totals by opcode, the hottest addresses, and any self-modified cells
+    pub fn report(&self) -> String {
+        let total = self.instructions();
+        let percent = |count: u64| 100.0 * count as f64 / total.max(1) as f64;
+        let mut report = String::new();
+        writeln!(report, "{} instructions executed", total).unwrap();
+
+        writeln!(report, "\nBy opcode:").unwrap();
+        let mut opcodes = self.opcode_counts.iter().collect::<Vec<_>>();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (opcode, count) in opcodes {
+            writeln!(
+                report,
+                "{:>8} {:>12} {:>6.2}%",
+                opcode.mnemonic(),
+                count,
+                percent(*count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nHottest addresses:").unwrap();
+        let mut pcs = self.pc_counts.iter().collect::<Vec<_>>();
+        pcs.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (pc, count) in pcs.into_iter().take(HOTTEST) {
+            writeln!(report, "{:>8} {:>12} {:>6.2}%", pc, count, percent(*count)).unwrap();
+        }
+
+        let modified = self.self_modified();
+        if !modified.is_empty() {
+            writeln!(report, "\nSelf-modified cells:").unwrap();
+            for (addr, count) in modified {
+                writeln!(report, "{:>8} {:>12} writes", addr, count).unwrap();
+            }
+        }
+        report
+    }
+
+    /// A disassembly of `code` with the number of times each instruction was executed in the
+    /// left margin. Instructions that never ran are marked with dashes.
+    pub fn annotate(&self, code: &[i64]) -> String {
+        let mut listing = String::new();
+        for line in self.disassemble(code).lines {
+            let count = match &line {
+                Line::Code(inst) => match self.pc_counts.get(&inst.addr) {
+                    Some(count) => count.to_string(),
+                    None => "-----".to_string(),
+                },
+                Line::Data { .. } => String::new(),
+            };
+            writeln!(
+                listing,
+                "{:>12} {:>6}: {}",
+                count,
+                line.addr(),
+                line.source()
+            )
+            .unwrap();
+        }
+        listing
+    }
+
+    fn disassemble(&self, code: &[i64]) -> Disassembly {
+        let entry_points = std::iter::once(0)
+            .chain(self.pc_counts.keys().cloned())
+            .collect::<Vec<_>>();
+        Disassembly::with_entry_points(code, &entry_points)
+    }
+}
+
+impl<C> Observer<C> for Profiler {
+    fn instruction(&mut self, pc: usize, opcode: Opcode) {
+        *self.pc_counts.entry(pc).or_insert(0) += 1;
+        *self.opcode_counts.entry(opcode).or_insert(0) += 1;
+        if self.last_opcode.insert(pc, opcode) != Some(opcode) {
+            self.executed_cells.extend(pc..=pc + opcode.num_params());
+        }
+    }
+
+    fn memory_write(&mut self, addr: usize, _old: &C, _new: &C) {
+        *self.writes.entry(addr).or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::Processor;
+    use maplit::btreemap;
+
+    /// Counts [14] down from 3, then outputs it and halts
+    fn countdown() -> Vec<i64> {
+        vec![1001, 14, -1, 14, 1005, 14, 0, 4, 14, 99, 104, 1, 99, 0, 3]
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut profiler = Profiler::new();
+        Processor::new(countdown())
+            .execute_observed(deque!(), &mut profiler)
+            .unwrap();
+        assert_eq!(profiler.instructions(), 8);
+        assert_eq!(
+            profiler.pc_counts,
+            btreemap! { 0 => 3, 4 => 3, 7 => 1, 9 => 1 }
+        );
+        assert_eq!(
+            profiler.opcode_counts,
+            btreemap! { Opcode::Add => 3, Opcode::Jnz => 3, Opcode::Output => 1, Opcode::Halt => 1 }
+        );
+        assert_eq!(profiler.writes, btreemap! { 14 => 3 });
+        assert_eq!(profiler.self_modified(), vec![]);
+        assert_eq!(profiler.unreached(&countdown()), vec![]);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let mut profiler = Profiler::new();
+        Processor::new(countdown())
+            .execute_observed(deque!(), &mut profiler)
+            .unwrap();
+        assert_eq!(
+            profiler.annotate(&countdown()),
+            "           3      0: add [14], #-1, [14]\n\
+             \x20          3      4: jnz [14], #0\n\
+             \x20          1      7: output [14]\n\
+             \x20          1      9: halt\n\
+             \x20                10: db 104, 1, 99, 0, 3\n"
+        );
+    }
+
+    #[test]
+    fn test_unreached() {
+        // Outputs 1 when given 0, and 2 otherwise
+        let code = vec![3, 11, 1005, 11, 8, 104, 1, 99, 104, 2, 99, 0];
+        let mut profiler = Profiler::new();
+        Processor::new(code.clone())
+            .execute_observed(deque!(0), &mut profiler)
+            .unwrap();
+        assert_eq!(profiler.unreached(&code), vec![8, 10]);
+    }
+
+    #[test]
+    fn test_self_modified() {
+        // Turns the add at 4 into a halt, after running it once
+        let code = vec![1101, 0, 0, 15, 1, 15, 15, 15, 1101, 0, 99, 4, 1105, 1, 4, 0];
+        let mut profiler = Profiler::new();
+        Processor::new(code)
+            .execute_observed(deque!(), &mut profiler)
+            .unwrap();
+        assert_eq!(profiler.self_modified(), vec![(4, 1)]);
+        assert_eq!(profiler.pc_counts[&4], 2);
+        assert!(profiler
+            .report()
+            .contains("Self-modified cells:\n       4            1 writes\n"));
+    }
+}
@@ -0,0 +1,454 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{IntcodeError, NoObserver, Observer, Opcode, Processor};
+
+/// The first line of every session file, identifying the format and its version
+const HEADER: &str = "intcode session v1";
+
+/// How many instructions past where the next event was recorded a replayed program may run
+/// without reading or writing anything before it is taken to have gone astray
+const REPLAY_MARGIN: u64 = 1_000_000;
+
+/// A value passing between a program and the outside world, along with the number of
+/// instructions executed up to and including the one that read or wrote it
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SessionEvent {
+    Input { instruction: u64, value: i64 },
+    Output { instruction: u64, value: i64 },
+}
+
+impl SessionEvent {
+    /// The number of instructions executed up to and including the one that read or wrote the
+    /// value
+    pub fn instruction(&self) -> u64 {
+        match self {
+            SessionEvent::Input { instruction, .. } | SessionEvent::Output { instruction, .. } => {
+                *instruction
+            }
+        }
+    }
+}
+
+impl Display for SessionEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionEvent::Input { instruction, value } => write!(f, "{} in {}", instruction, value),
+            SessionEvent::Output { instruction, value } => {
+                write!(f, "{} out {}", instruction, value)
+            }
+        }
+    }
+}
+
+/// Everything a program read and wrote over a run, which can be saved and replayed later to
+/// reproduce the run exactly without whatever originally supplied the input.
+///
+/// Sessions are written as plain text, one event per line:
+///
+/// ```text
+/// intcode session v1
+/// 1 in 5
+/// 3 out 10
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Session {
+    pub events: Vec<SessionEvent>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    /// Run `proc` from where it is, feeding it the recorded input and checking each value it
+    /// reads and writes against the recording. Stops at the first difference, or once the
+    /// program halts or wants more input than was recorded.
+    ///
+    /// A program that runs far past where the next event was recorded without reading or writing
+    /// anything, such as one caught in a loop, is stopped and reported as stalled.
+    pub fn replay(&self, proc: &mut Processor) -> Result<Option<Mismatch>, IntcodeError> {
+        let mut input = self
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                SessionEvent::Input { value, .. } => Some(*value),
+                SessionEvent::Output { .. } => None,
+            })
+            .collect::<VecDeque<i64>>();
+        let mut recorder = Recorder::new();
+        let mut output = Vec::new();
+        let mut checked = 0;
+        loop {
+            let deadline = self
+                .events
+                .get(checked)
+                .or_else(|| self.events.last())
+                .map_or(0, |event| event.instruction())
+                + REPLAY_MARGIN;
+            if recorder.instructions() >= deadline {
+                return Ok(Some(Mismatch {
+                    index: checked,
+                    expected: self.events.get(checked).cloned(),
+                    actual: None,
+                    stalled: true,
+                }));
+            }
+            let state = proc.step_observed(&mut input, &mut output, &mut recorder)?;
+            for actual in &recorder.session.events[checked..] {
+                let expected = self.events.get(checked);
+                if expected != Some(actual) {
+                    return Ok(Some(Mismatch {
+                        index: checked,
+                        expected: expected.cloned(),
+                        actual: Some(*actual),
+                        stalled: false,
+                    }));
+                }
+                checked += 1;
+            }
+            if state.is_some() {
+                break;
+            }
+        }
+
+        Ok(self.events.get(checked).map(|expected| Mismatch {
+            index: checked,
+            expected: Some(*expected),
+            actual: None,
+            stalled: false,
+        }))
+    }
+
+    /// Load the session saved at `path` and replay it on a processor loaded with `code`
+    pub fn replay_file<P: AsRef<Path>>(
+        path: P,
+        code: Vec<i64>,
+    ) -> Result<Option<Mismatch>, SessionError> {
+        Session::load(path)?
+            .replay(&mut Processor::new(code))
+            .map_err(SessionError::Program)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SessionError> {
+        std::fs::write(path, self.to_string()).map_err(SessionError::Io)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Session, SessionError> {
+        std::fs::read_to_string(path)
+            .map_err(SessionError::Io)?
+            .parse()
+    }
+}
+
+impl Display for Session {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Session {
+    type Err = SessionError;
+
+    fn from_str(text: &str) -> Result<Session, SessionError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, header)) if header.trim() == HEADER => {}
+            Some((_, header)) => {
+                return Err(SessionError::Format {
+                    line: 1,
+                    message: format!("expected '{}' but got '{}'", HEADER, header),
+                })
+            }
+            None => {
+                return Err(SessionError::Format {
+                    line: 1,
+                    message: "empty session".to_string(),
+                })
+            }
+        }
+
+        let mut events = Vec::new();
+        for (i, text) in lines {
+            let error = |message: String| SessionError::Format {
+                line: i + 1,
+                message,
+            };
+            let parts = text.split_whitespace().collect::<Vec<&str>>();
+            if parts.len() != 3 {
+                return Err(error(format!(
+                    "expected 'instruction in|out value' but got '{}'",
+                    text
+                )));
+            }
+            let instruction = parts[0]
+                .parse::<u64>()
+                .map_err(|_| error(format!("invalid instruction count '{}'", parts[0])))?;
+            let value = parts[2]
+                .parse::<i64>()
+                .map_err(|_| error(format!("invalid number '{}'", parts[2])))?;
+            events.push(match parts[1] {
+                "in" => SessionEvent::Input { instruction, value },
+                "out" => SessionEvent::Output { instruction, value },
+                other => return Err(error(format!("expected 'in' or 'out' but got '{}'", other))),
+            });
+        }
+        Ok(Session { events })
+    }
+}
+
+/// The ways in which reading, writing or replaying a session can fail
+#[derive(Debug)]
+pub enum SessionError {
+    Io(std::io::Error),
+    /// Line `line` (counting from 1) of the session could not be understood
+    Format {
+        line: usize,
+        message: String,
+    },
+    /// The program failed while it was being replayed
+    Program(IntcodeError),
+    /// The program did not behave as it did when the session was recorded
+    Mismatch(Mismatch),
+}
+
+impl Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SessionError::Io(err) => write!(f, "{}", err),
+            SessionError::Format { line, message } => write!(f, "Line {}: {}", line, message),
+            SessionError::Program(err) => write!(f, "{}", err),
+            SessionError::Mismatch(mismatch) => write!(f, "{}", mismatch),
+        }
+    }
+}
+
+impl Error for SessionError {}
+
+/// The first point at which a replayed run differs from its recording
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Mismatch {
+    /// The position of the differing event in the recording
+    pub index: usize,
+    /// The recorded event, or `None` if the replayed run read or wrote more than was recorded
+    pub expected: Option<SessionEvent>,
+    /// The replayed event, or `None` if the replayed run stopped before reaching it
+    pub actual: Option<SessionEvent>,
+    /// Whether the replayed run was stopped for going on too long without reading or writing
+    /// anything, rather than stopping by itself
+    pub stalled: bool,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Event {}: expected ", self.index)?;
+        match &self.expected {
+            Some(event) => write!(f, "'{}'", event)?,
+            None => write!(f, "the end of the session")?,
+        }
+        match &self.actual {
+            Some(event) => write!(f, " but got '{}'", event),
+            None if self.stalled => write!(f, " but the program stalled"),
+            None => write!(f, " but the program stopped"),
+        }
+    }
+}
+
+/// What a program runner was asked to do on the command line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SessionMode {
+    /// Run the program as usual
+    Run,
+    /// Run the program, recording everything it reads and writes to this session file
+    Record(String),
+    /// Check that the program behaves exactly as it did in this session file
+    Replay(String),
+}
+
+impl SessionMode {
+    /// The arguments `from_args` understands, for a usage message
+    pub const USAGE: &'static str = "[--record <session> | --replay <session>]";
+
+    /// Parse the arguments following the program name, or `None` if they are not understood
+    pub fn from_args(args: &[String]) -> Option<SessionMode> {
+        match args {
+            [] => Some(SessionMode::Run),
+            [flag, path] if flag == "--record" => Some(SessionMode::Record(path.clone())),
+            [flag, path] if flag == "--replay" => Some(SessionMode::Replay(path.clone())),
+            _ => None,
+        }
+    }
+
+    /// Run `code` in this mode. `run` runs the program as usual, passing the observer it is
+    /// given on to the processor, and its result is returned. When replaying, `run` is not
+    /// called, and `None` is returned if the program behaves as recorded.
+    pub fn run<T, F>(&self, code: Vec<i64>, run: F) -> Result<Option<T>, SessionError>
+    where
+        F: FnOnce(Vec<i64>, &mut dyn Observer<i64>) -> T,
+    {
+        match self {
+            SessionMode::Run => Ok(Some(run(code, &mut NoObserver))),
+            SessionMode::Record(path) => {
+                let mut recorder = Recorder::new();
+                let result = run(code, &mut recorder);
+                recorder.session.save(path)?;
+                Ok(Some(result))
+            }
+            SessionMode::Replay(path) => match Session::replay_file(path, code)? {
+                None => Ok(None),
+                Some(mismatch) => Err(SessionError::Mismatch(mismatch)),
+            },
+        }
+    }
+}
+
+/// Records a session as a program runs. The instruction count carries on across runs, so a
+/// single recorder can follow a program through every time it suspends for input.
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    pub session: Session,
+    instructions: u64,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    /// The number of instructions executed so far
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+}
+
+impl Observer<i64> for Recorder {
+    fn instruction(&mut self, _pc: usize, _opcode: Opcode) {
+        self.instructions += 1;
+    }
+
+    fn input(&mut self, value: &i64) {
+        self.session.events.push(SessionEvent::Input {
+            instruction: self.instructions,
+            value: *value,
+        });
+    }
+
+    fn output(&mut self, value: &i64) {
+        self.session.events.push(SessionEvent::Output {
+            instruction: self.instructions,
+            value: *value,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::ProcessorState;
+
+    /// Reads a number and outputs double it, forever
+    fn doubler() -> Vec<i64> {
+        vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0]
+    }
+
+    fn record(input: Vec<i64>) -> Session {
+        let mut recorder = Recorder::new();
+        let (state, _) = Processor::new(doubler())
+            .execute_observed(input.into_iter().collect(), &mut recorder)
+            .unwrap();
+        assert_eq!(state, ProcessorState::IoWait);
+        recorder.session
+    }
+
+    #[test]
+    fn test_record() {
+        let session = record(vec![3, 5]);
+        assert_eq!(
+            session.to_string(),
+            "intcode session v1\n1 in 3\n3 out 6\n5 in 5\n7 out 10\n"
+        );
+        assert_eq!(session.to_string().parse::<Session>().unwrap(), session);
+    }
+
+    #[test]
+    fn test_replay() {
+        let session = record(vec![3, 5]);
+        let mut proc = Processor::new(doubler());
+        assert_eq!(session.replay(&mut proc), Ok(None));
+
+        // Tripling instead of doubling shows up at the first output
+        let mut code = doubler();
+        code[4] = 3;
+        assert_eq!(
+            session.replay(&mut Processor::new(code)),
+            Ok(Some(Mismatch {
+                index: 1,
+                expected: Some(SessionEvent::Output {
+                    instruction: 3,
+                    value: 6
+                }),
+                actual: Some(SessionEvent::Output {
+                    instruction: 3,
+                    value: 9
+                }),
+                stalled: false,
+            }))
+        );
+
+        // Halting after the first output leaves the rest of the session unplayed
+        let mut code = doubler();
+        code[8] = 99;
+        let mismatch = session.replay(&mut Processor::new(code)).unwrap().unwrap();
+        assert_eq!(mismatch.index, 2);
+        assert_eq!(mismatch.actual, None);
+        assert_eq!(
+            mismatch.to_string(),
+            "Event 2: expected '5 in 5' but the program stopped"
+        );
+    }
+
+    #[test]
+    fn test_replay_stalls() {
+        // Looping forever instead of reading the second number never reaches the next event
+        let session = record(vec![3, 5]);
+        let mut code = doubler();
+        code[10] = 8;
+        let mismatch = session.replay(&mut Processor::new(code)).unwrap().unwrap();
+        assert_eq!(mismatch.index, 2);
+        assert!(mismatch.stalled);
+        assert_eq!(
+            mismatch.to_string(),
+            "Event 2: expected '5 in 5' but the program stalled"
+        );
+    }
+
+    #[test]
+    fn test_mode_from_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(SessionMode::from_args(&args(&[])), Some(SessionMode::Run));
+        assert_eq!(
+            SessionMode::from_args(&args(&["--replay", "game.txt"])),
+            Some(SessionMode::Replay("game.txt".to_string()))
+        );
+        assert_eq!(SessionMode::from_args(&args(&["--record"])), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = "intcode session v1\n1 in 3\n3 sideways 6\n"
+            .parse::<Session>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Line 3: expected 'in' or 'out' but got 'sideways'"
+        );
+        assert!("intcode snapshot v1\n".parse::<Session>().is_err());
+    }
+}
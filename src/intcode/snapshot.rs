@@ -0,0 +1,408 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{Cell, Memory, Processor};
+
+/// The first line of every snapshot, identifying the format and its version
+const HEADER: &str = "intcode snapshot v1";
+
+/// The number of memory cells written on each line, chosen so that a change to a single cell
+/// shows up as a single changed line when two snapshots are diffed. Lines holding nothing but
+/// zeros are left out.
+const CELLS_PER_LINE: usize = 8;
+
+/// The complete state of a suspended Intcode machine, including any input it has yet to consume
+/// and any output the caller has yet to process.
+///
+/// Snapshots are written as plain text:
+///
+/// ```text
+/// intcode snapshot v1
+/// pc: 0
+/// rel: 0
+/// input: 1 2
+/// output:
+/// limit: 65536
+/// memory: 5002
+///      0: 3 9 1001 9 5 10 4 10
+///      8: 99 0
+///   5000: 0 7
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Snapshot<C: Cell = i64> {
+    pub memory: Memory<C>,
+    pub pc: usize,
+    pub rel: i64,
+    pub input: VecDeque<C>,
+    pub output: VecDeque<C>,
+}
+
+impl<C: Cell> Snapshot<C> {
+    /// Capture the state of `proc` along with the input and output queues surrounding it
+    pub fn capture(proc: &Processor<C>, input: &VecDeque<C>, output: &VecDeque<C>) -> Snapshot<C> {
+        Snapshot {
+            memory: proc.memory().clone(),
+            pc: proc.pc(),
+            rel: proc.rel(),
+            input: input.clone(),
+            output: output.clone(),
+        }
+    }
+
+    /// Build a processor that will carry on exactly where the captured one left off
+    pub fn processor(&self) -> Processor<C> {
+        Processor::with_state(self.memory.clone(), self.pc, self.rel)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        std::fs::write(path, self.to_string()).map_err(SnapshotError::Io)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Snapshot<C>, SnapshotError> {
+        std::fs::read_to_string(path)
+            .map_err(SnapshotError::Io)?
+            .parse()
+    }
+}
+
+fn join<'a, C: Cell + 'a, I: IntoIterator<Item = &'a C>>(values: I) -> String {
+    values
+        .into_iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl<C: Cell> Display for Snapshot<C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "pc: {}", self.pc)?;
+        writeln!(f, "rel: {}", self.rel)?;
+        writeln!(f, "{}", format!("input: {}", join(&self.input)).trim_end())?;
+        writeln!(
+            f,
+            "{}",
+            format!("output: {}", join(&self.output)).trim_end()
+        )?;
+        writeln!(f, "limit: {}", self.memory.max_pages())?;
+        writeln!(f, "memory: {}", self.memory.extent())?;
+        let mut line_starts = self
+            .memory
+            .nonzero()
+            .into_iter()
+            .map(|(addr, _)| addr - addr % CELLS_PER_LINE)
+            .collect::<Vec<usize>>();
+        line_starts.dedup();
+        for start in line_starts {
+            let end = (start + CELLS_PER_LINE).min(self.memory.extent());
+            let values = (start..end)
+                .map(|addr| self.memory.get(addr))
+                .collect::<Vec<C>>();
+            writeln!(f, "{:>6}: {}", start, join(&values))?;
+        }
+        Ok(())
+    }
+}
+
+/// The ways in which reading or writing a snapshot can fail
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    /// Line `line` (counting from 1) of the snapshot could not be understood
+    Format {
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{}", err),
+            SnapshotError::Format { line, message } => write!(f, "Line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+/// Walks the lines of a snapshot, keeping track of the line number for error reporting
+struct Reader<'a> {
+    lines: std::iter::Enumerate<std::str::Lines<'a>>,
+    line: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error<T>(&self, message: String) -> Result<T, SnapshotError> {
+        Err(SnapshotError::Format {
+            line: self.line,
+            message,
+        })
+    }
+
+    fn next_line(&mut self) -> Result<&'a str, SnapshotError> {
+        match self.lines.next() {
+            Some((i, text)) => {
+                self.line = i + 1;
+                Ok(text)
+            }
+            None => {
+                self.line += 1;
+                self.error("unexpected end of snapshot".to_string())
+            }
+        }
+    }
+
+    /// Split a `key: value` line into its trimmed key and value
+    fn split(&self, text: &'a str) -> Result<(&'a str, &'a str), SnapshotError> {
+        let mut parts = text.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => Ok((key.trim(), value.trim())),
+            _ => self.error(format!("expected 'key: value' but got '{}'", text)),
+        }
+    }
+
+    /// Read a `key: value` line with the given key, returning the value
+    fn field(&mut self, key: &str) -> Result<&'a str, SnapshotError> {
+        let text = self.next_line()?;
+        match self.split(text)? {
+            (found, value) if found == key => Ok(value),
+            _ => self.error(format!("expected '{}:' but got '{}'", key, text)),
+        }
+    }
+
+    fn parse<T: FromStr>(&self, text: &str) -> Result<T, SnapshotError> {
+        match text.parse::<T>() {
+            Ok(value) => Ok(value),
+            Err(_) => self.error(format!("invalid number '{}'", text)),
+        }
+    }
+
+    fn parse_values<C: Cell>(&self, text: &str) -> Result<Vec<C>, SnapshotError> {
+        text.split_whitespace()
+            .map(|value| self.parse(value))
+            .collect()
+    }
+}
+
+impl<C: Cell> FromStr for Snapshot<C> {
+    type Err = SnapshotError;
+
+    fn from_str(text: &str) -> Result<Snapshot<C>, SnapshotError> {
+        let mut reader = Reader {
+            lines: text.lines().enumerate(),
+            line: 0,
+        };
+
+        let header = reader.next_line()?;
+        if header.trim() != HEADER {
+            return reader.error(format!("expected '{}' but got '{}'", HEADER, header));
+        }
+        let pc = reader.field("pc").and_then(|pc| reader.parse(pc))?;
+        let rel = reader.field("rel").and_then(|rel| reader.parse(rel))?;
+        let input = reader
+            .field("input")
+            .and_then(|input| reader.parse_values(input))?;
+        let output = reader
+            .field("output")
+            .and_then(|output| reader.parse_values(output))?;
+        let max_pages = reader
+            .field("limit")
+            .and_then(|limit| reader.parse(limit))?;
+        let extent: usize = reader
+            .field("memory")
+            .and_then(|extent| reader.parse(extent))?;
+
+        let mut cells: Vec<(usize, C)> = Vec::new();
+        let mut next_start = 0;
+        while let Some((i, text)) = reader.lines.next() {
+            reader.line = i + 1;
+            let (start, values) = reader.split(text)?;
+            let start: usize = reader.parse(start)?;
+            if !start.is_multiple_of(CELLS_PER_LINE) || start < next_start || start >= extent {
+                return reader.error(format!("unexpected memory address {}", start));
+            }
+            let values: Vec<C> = reader.parse_values(values)?;
+            let expected = CELLS_PER_LINE.min(extent - start);
+            if values.len() != expected {
+                return reader.error(format!(
+                    "expected {} values at {} but got {}",
+                    expected,
+                    start,
+                    values.len()
+                ));
+            }
+            cells.extend(
+                values
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, value)| !value.is_zero())
+                    .map(|(offset, value)| (start + offset, value)),
+            );
+            next_start = start + CELLS_PER_LINE;
+        }
+
+        Ok(Snapshot {
+            memory: Memory::from_nonzero(extent, &cells, max_pages),
+            pc,
+            rel,
+            input: input.into_iter().collect(),
+            output: output.into_iter().collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::ProcessorState;
+
+    #[test]
+    fn test_format() {
+        let mut proc = Processor::new(vec![3, 9, 1001, 9, 5, 10, 4, 10, 99, 0]);
+        let mut input = deque!();
+        let mut output = deque!();
+        assert_eq!(
+            proc.step(&mut input, &mut output).unwrap(),
+            Some(ProcessorState::IoWait)
+        );
+        input.extend(vec![1, 2]);
+        assert_eq!(
+            Snapshot::capture(&proc, &input, &output).to_string(),
+            "intcode snapshot v1
+pc: 0
+rel: 0
+input: 1 2
+output:
+limit: 65536
+memory: 10
+     0: 3 9 1001 9 5 10 4 10
+     8: 99 0
+"
+        )
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let code =
+            crate::intcode::parse_program(&std::fs::read_to_string("src/bin/day09.txt").unwrap())
+                .unwrap();
+        let mut proc = Processor::new(code);
+        let mut input = deque!();
+        let mut output = deque!();
+        proc.step(&mut input, &mut output).unwrap();
+        input.push_back(1);
+        for _ in 0..100 {
+            proc.step(&mut input, &mut output).unwrap();
+        }
+
+        let snapshot = Snapshot::capture(&proc, &input, &output);
+        let restored: Snapshot = snapshot.to_string().parse().unwrap();
+        assert_eq!(restored, snapshot);
+        assert_eq!(
+            restored
+                .processor()
+                .execute(restored.input.clone())
+                .unwrap(),
+            proc.execute(input).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resume_after_restore() {
+        let mut proc = Processor::new(vec![3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99, 0, 0, 0]);
+        assert_eq!(
+            proc.execute(deque!(20)).unwrap(),
+            (ProcessorState::IoWait, deque!())
+        );
+
+        let path = std::env::temp_dir().join(format!(
+            "intcode-snapshot-{}-resume-after-restore.txt",
+            std::process::id()
+        ));
+        Snapshot::capture(&proc, &deque!(), &deque!())
+            .save(&path)
+            .unwrap();
+        let snapshot: Snapshot = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((snapshot.pc, snapshot.rel), (2, 0));
+        assert_eq!(
+            snapshot.processor().execute(deque!(22)).unwrap(),
+            (ProcessorState::Halted, deque!(42))
+        );
+    }
+
+    #[test]
+    fn test_bad_header() {
+        match "intcode snapshot v2\n".parse::<Snapshot>() {
+            Err(SnapshotError::Format { line: 1, .. }) => (),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_bad_number() {
+        match "intcode snapshot v1\npc: 0\nrel: x\n".parse::<Snapshot>() {
+            Err(SnapshotError::Format { line: 3, message }) => {
+                assert_eq!(message, "invalid number 'x'")
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sparse_memory() {
+        let mut proc = Processor::new(vec![1101, 3, 4, 1_000_000, 99]);
+        proc.execute(deque!()).unwrap();
+        let text = Snapshot::capture(&proc, &deque!(), &deque!()).to_string();
+        assert!(text.ends_with("memory: 1000001\n     0: 1101 3 4 1000000 99 0 0 0\n1000000: 7\n"));
+        let restored: Snapshot = text.parse().unwrap();
+        assert_eq!(restored.memory, *proc.memory());
+    }
+
+    #[test]
+    fn test_big_int_round_trip() {
+        let big = "123456789012345678901234567890"
+            .parse::<num::BigInt>()
+            .unwrap();
+        let proc = Processor::with_memory(Memory::new(&[num::BigInt::from(99), -big.clone()]));
+        let snapshot = Snapshot::capture(&proc, &deque!(big), &deque!());
+        let text = snapshot.to_string();
+        assert!(text.contains("input: 123456789012345678901234567890\n"));
+        assert!(text.ends_with("     0: 99 -123456789012345678901234567890\n"));
+        assert_eq!(text.parse::<Snapshot<num::BigInt>>().unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_short_memory_line() {
+        let text = concat!(
+            "intcode snapshot v1\npc: 0\nrel: 0\ninput:\noutput:\nlimit: 4\nmemory: 10\n",
+            "     0: 1 2 3\n",
+        );
+        match text.parse::<Snapshot>() {
+            Err(SnapshotError::Format { line: 8, message }) => {
+                assert_eq!(message, "expected 8 values at 0 but got 3")
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_misplaced_memory_line() {
+        let text = concat!(
+            "intcode snapshot v1\npc: 0\nrel: 0\ninput:\noutput:\nlimit: 4\nmemory: 20\n",
+            "     8: 1 2 3 4 5 6 7 8\n",
+            "     0: 1 2 3 4 5 6 7 8\n",
+        );
+        match text.parse::<Snapshot>() {
+            Err(SnapshotError::Format { line: 9, message }) => {
+                assert_eq!(message, "unexpected memory address 0")
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,520 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+use super::{
+    Expr, IntcodeError, Memory, PathEnd, Processor, ProcessorState, SymbolicExecutor, Var,
+};
+
+/// Where a program ended up after running with one assignment of the varied cells
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Outcome {
+    pub state: ProcessorState,
+    pub memory: Memory,
+    pub output: Vec<i64>,
+}
+
+/// A single value read from an `Outcome`, for goals of the form "this value equals that"
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Target {
+    /// The final value of the cell at this address
+    Memory(usize),
+    /// The output at this position, counting from 0
+    Output(usize),
+}
+
+impl Target {
+    /// The value of the target in `outcome`, or `None` if the program did not halt or did not
+    /// output that many values
+    pub fn value(&self, outcome: &Outcome) -> Option<i64> {
+        if outcome.state != ProcessorState::Halted {
+            return None;
+        }
+        match *self {
+            Target::Memory(addr) => Some(outcome.memory.get(addr)),
+            Target::Output(index) => outcome.output.get(index).cloned(),
+        }
+    }
+}
+
+/// A target that was found to be `constant + coefficients[0] * cells[0] + ...`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Affine {
+    pub constant: i64,
+    pub coefficients: Vec<i64>,
+}
+
+impl Affine {
+    fn value(&self, values: &[i64]) -> i128 {
+        self.coefficients
+            .iter()
+            .zip(values)
+            .map(|(&c, &x)| c as i128 * x as i128)
+            .sum::<i128>()
+            + self.constant as i128
+    }
+}
+
+/// The ways in which a search can fail
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SearchError {
+    /// There are more assignments of the varied cells than can be counted
+    TooManyAssignments,
+}
+
+impl Display for SearchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SearchError::TooManyAssignments => write!(f, "Too many assignments to search"),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+/// Searches for the initial values of some memory cells that make a program reach a goal
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct GoalSeek {
+    /// The address of each cell to vary, with the values it may take
+    pub cells: Vec<(usize, RangeInclusive<i64>)>,
+    /// Input given to the program on every run
+    pub input: Vec<i64>,
+    /// How many threads to spread an exhaustive search across
+    pub threads: usize,
+    /// How many instructions a single run may execute before it is abandoned, or `None` to let
+    /// every run go on for as long as it likes
+    pub budget: Option<u64>,
+}
+
+impl GoalSeek {
+    /// A search over `cells` with no input, using every available core and allowing each run
+    /// a million instructions
+    pub fn new(cells: Vec<(usize, RangeInclusive<i64>)>) -> GoalSeek {
+        GoalSeek {
+            cells,
+            input: Vec::new(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            budget: Some(1_000_000),
+        }
+    }
+
+    /// Run `code` with the varied cells set to `values`, given in the same order as `cells`.
+    /// A run that uses up the budget stops in `ProcessorState::BudgetExhausted`.
+    pub fn evaluate(&self, code: &[i64], values: &[i64]) -> Result<Outcome, IntcodeError> {
+        let mut memory = Memory::new(code);
+        for ((addr, _), value) in self.cells.iter().zip(values) {
+            if !memory.set(*addr, *value) {
+                return Err(IntcodeError::OutOfMemory {
+                    pc: 0,
+                    addr: *addr,
+                    max_pages: memory.max_pages(),
+                });
+            }
+        }
+
+        let mut proc = Processor::with_memory(memory);
+        proc.set_instruction_budget(self.budget);
+        let (state, output) = proc.execute(self.input.iter().cloned().collect::<VecDeque<_>>())?;
+        Ok(Outcome {
+            state,
+            memory: proc.memory().clone(),
+            output: output.into(),
+        })
+    }
+
+    /// Try every assignment of the varied cells, returning those whose outcome satisfies
+    /// `predicate` in ascending order. Assignments that make the program fail or use up the
+    /// budget are skipped without consulting `predicate`.
+    pub fn search<P>(&self, code: &[i64], predicate: P) -> Result<Vec<Vec<i64>>, SearchError>
+    where
+        P: Fn(&Outcome) -> bool + Sync,
+    {
+        let threads = self.threads.max(1);
+        let count = self.count(0..self.cells.len())?;
+        let predicate = &predicate;
+        let mut found = std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|worker| {
+                    scope.spawn(move || {
+                        (worker..count)
+                            .step_by(threads)
+                            .map(|index| self.assignment(index, 0..self.cells.len()))
+                            .filter(|values| match self.evaluate(code, values) {
+                                Ok(outcome) => {
+                                    outcome.state != ProcessorState::BudgetExhausted
+                                        && predicate(&outcome)
+                                }
+                                Err(_) => false,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        found.sort();
+        Ok(found)
+    }
+
+    /// Prove that `target` is an affine function of the varied cells, by executing the program
+    /// symbolically with those cells unknown. Every path that halts must give the target the
+    /// same affine value, so that the function holds for every assignment under which the
+    /// program halts. Gives `None` if that cannot be shown, such as when the cells are used as
+    /// addresses or the program has too many paths to explore.
+    pub fn affine(&self, code: &[i64], target: Target) -> Option<Affine> {
+        let executor = SymbolicExecutor {
+            symbolic_cells: self.cells.iter().map(|(addr, _)| *addr).collect(),
+            ..SymbolicExecutor::new()
+        };
+        let paths = executor.explore(code);
+        if paths.len() >= executor.max_paths {
+            return None;
+        }
+
+        let mut affine = None;
+        for path in paths {
+            match path.end {
+                PathEnd::Halted => (),
+                // Errors are only reported from known values, so assignments that follow this path
+                // fail when run too, and never reach the target
+                PathEnd::Error(_) => continue,
+                PathEnd::Unresolved { .. } | PathEnd::StepLimit => return None,
+            }
+            // A path that reads more input than is given is never taken
+            if path.inputs > self.input.len() {
+                continue;
+            }
+            let expr = match target {
+                Target::Memory(addr) => match path.memory.get(&addr) {
+                    Some(expr) => expr.clone(),
+                    None => Expr::Const(code.get(addr).cloned().unwrap_or(0)),
+                },
+                Target::Output(index) => match path.outputs.get(index) {
+                    Some(expr) => expr.clone(),
+                    None => continue,
+                },
+            };
+            let found = self.to_affine(&expr)?;
+            if *affine.get_or_insert_with(|| found.clone()) != found {
+                return None;
+            }
+        }
+        affine
+    }
+
+    /// `expr` in terms of the varied cells, with the given input substituted for any values read
+    fn to_affine(&self, expr: &Expr) -> Option<Affine> {
+        let (mut constant, terms) = expr.linear()?;
+        let mut coefficients = vec![0i128; self.cells.len()];
+        for (var, coefficient) in terms {
+            match var {
+                Var::Cell(addr) => {
+                    let i = self.cells.iter().position(|(cell, _)| *cell == addr)?;
+                    coefficients[i] += coefficient;
+                }
+                Var::Input(n) => {
+                    let term = coefficient.checked_mul(*self.input.get(n)? as i128)?;
+                    constant = constant.checked_add(term)?;
+                }
+            }
+        }
+        Some(Affine {
+            constant: i64::try_from(constant).ok()?,
+            coefficients: coefficients
+                .into_iter()
+                .map(|c| i64::try_from(c).ok())
+                .collect::<Option<Vec<_>>>()?,
+        })
+    }
+
+    /// Every assignment of the varied cells for which `target` ends up equal to `value`, in
+    /// ascending order.
+    ///
+    /// When the target can be proven affine in the cells, only the values of all but one cell
+    /// need to be enumerated, as the last can be solved for directly. Each solution found that
+    /// way is run to confirm it. Otherwise this falls back to an exhaustive `search`.
+    pub fn solve(
+        &self,
+        code: &[i64],
+        target: Target,
+        value: i64,
+    ) -> Result<Vec<Vec<i64>>, SearchError> {
+        let affine = self.affine(code, target);
+        let pivot = affine
+            .as_ref()
+            .and_then(|affine| affine.coefficients.iter().rposition(|&c| c != 0));
+        let (affine, pivot) = match (affine, pivot) {
+            (Some(affine), Some(pivot)) => (affine, pivot),
+            _ => return self.search(code, |outcome| target.value(outcome) == Some(value)),
+        };
+
+        let others = (0..self.cells.len())
+            .filter(|&i| i != pivot)
+            .collect::<Vec<_>>();
+        let coefficient = affine.coefficients[pivot] as i128;
+        let mut found = Vec::new();
+        for index in 0..self.count(others.iter().cloned())? {
+            let mut values = vec![0; self.cells.len()];
+            for (i, x) in others
+                .iter()
+                .zip(self.assignment(index, others.iter().cloned()))
+            {
+                values[*i] = x;
+            }
+            let remainder = value as i128 - affine.value(&values);
+            if remainder % coefficient != 0 {
+                continue;
+            }
+            let x = remainder / coefficient;
+            if x < *self.cells[pivot].1.start() as i128 || x > *self.cells[pivot].1.end() as i128 {
+                continue;
+            }
+            values[pivot] = x as i64;
+            let confirmed = self
+                .evaluate(code, &values)
+                .is_ok_and(|outcome| target.value(&outcome) == Some(value));
+            if confirmed {
+                found.push(values);
+            }
+        }
+        found.sort();
+        Ok(found)
+    }
+
+    /// How many assignments there are of the cells at `indices`
+    fn count<I: IntoIterator<Item = usize>>(&self, indices: I) -> Result<usize, SearchError> {
+        indices.into_iter().try_fold(1usize, |total, i| {
+            GoalSeek::range_len(&self.cells[i].1)
+                .and_then(|len| total.checked_mul(len))
+                .ok_or(SearchError::TooManyAssignments)
+        })
+    }
+
+    /// The assignment at position `index` in the ascending order of assignments of the cells at
+    /// `indices`, with the last cell varying fastest
+    fn assignment<I>(&self, mut index: usize, indices: I) -> Vec<i64>
+    where
+        I: IntoIterator<Item = usize>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let mut values = indices
+            .into_iter()
+            .rev()
+            .map(|i| {
+                let range = &self.cells[i].1;
+                let len = GoalSeek::range_len(range).expect("Assignments counted before use");
+                let value = range.start() + (index % len) as i64;
+                index /= len;
+                value
+            })
+            .collect::<Vec<_>>();
+        values.reverse();
+        values
+    }
+
+    /// How many values are in `range`, or `None` if there are too many to count
+    fn range_len(range: &RangeInclusive<i64>) -> Option<usize> {
+        if range.is_empty() {
+            Some(0)
+        } else {
+            usize::try_from(*range.end() as i128 - *range.start() as i128 + 1).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<i64> {
+        crate::intcode::parse_program(text).unwrap()
+    }
+
+    /// Sets [0] to 100 * [13] + [14] + 5
+    fn noun_verb() -> Vec<i64> {
+        parse("1002,13,100,13,1,13,14,0,1001,0,5,0,99,0,0")
+    }
+
+    #[test]
+    fn test_affine() {
+        let seek = GoalSeek::new(vec![(13, 0..=99), (14, 0..=99)]);
+        assert_eq!(
+            seek.affine(&noun_verb(), Target::Memory(0)),
+            Some(Affine {
+                constant: 5,
+                coefficients: vec![100, 1]
+            })
+        );
+        assert_eq!(
+            seek.solve(&noun_verb(), Target::Memory(0), 1234).unwrap(),
+            vec![vec![12, 29]]
+        );
+    }
+
+    #[test]
+    fn test_affine_ranges_not_from_zero() {
+        let seek = GoalSeek::new(vec![(13, 10..=20), (14, -5..=5)]);
+        assert_eq!(
+            seek.affine(&noun_verb(), Target::Memory(0)),
+            Some(Affine {
+                constant: 5,
+                coefficients: vec![100, 1]
+            })
+        );
+        assert_eq!(
+            seek.solve(&noun_verb(), Target::Memory(0), 1502).unwrap(),
+            vec![vec![15, -3]]
+        );
+    }
+
+    #[test]
+    fn test_all_solutions() {
+        let seek = GoalSeek::new(vec![(13, 0..=99), (14, 0..=199)]);
+        let expected = vec![vec![11, 129], vec![12, 29]];
+        assert_eq!(
+            seek.solve(&noun_verb(), Target::Memory(0), 1234).unwrap(),
+            expected
+        );
+        assert_eq!(
+            seek.search(&noun_verb(), |outcome| outcome.memory.get(0) == 1234)
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_not_affine() {
+        // Sets [0] to [5] * [6]
+        let code = parse("2,5,6,0,99,0,0");
+        let seek = GoalSeek::new(vec![(5, 0..=12), (6, 0..=12)]);
+        assert_eq!(seek.affine(&code, Target::Memory(0)), None);
+        assert_eq!(
+            seek.solve(&code, Target::Memory(0), 12).unwrap(),
+            vec![
+                vec![1, 12],
+                vec![2, 6],
+                vec![3, 4],
+                vec![4, 3],
+                vec![6, 2],
+                vec![12, 1]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_target() {
+        // Outputs [7] + 3
+        let code = parse("1001,7,3,7,4,7,99,0");
+        let seek = GoalSeek::new(vec![(7, 0..=9)]);
+        assert_eq!(
+            seek.solve(&code, Target::Output(0), 10).unwrap(),
+            vec![vec![7]]
+        );
+        assert_eq!(
+            seek.solve(&code, Target::Output(1), 10).unwrap(),
+            Vec::<Vec<i64>>::new()
+        );
+    }
+
+    #[test]
+    fn test_failing_assignments_skipped() {
+        let code = parse("1,0,0,0,99");
+        let seek = GoalSeek::new(vec![(0, 0..=3)]);
+        assert_eq!(
+            seek.search(&code, |outcome| outcome.state == ProcessorState::Halted)
+                .unwrap(),
+            vec![vec![1], vec![2]]
+        );
+    }
+
+    #[test]
+    fn test_budget_exhausted_skipped() {
+        // Loops forever if [4] is 0, otherwise halts
+        let code = parse("1006,4,0,99,0");
+        let mut seek = GoalSeek::new(vec![(4, 0..=2)]);
+        seek.budget = Some(100);
+        assert_eq!(
+            seek.evaluate(&code, &[0]).unwrap().state,
+            ProcessorState::BudgetExhausted
+        );
+        assert_eq!(
+            seek.search(&code, |_| true).unwrap(),
+            vec![vec![1], vec![2]]
+        );
+        assert_eq!(
+            seek.solve(&code, Target::Memory(4), 0).unwrap(),
+            Vec::<Vec<i64>>::new()
+        );
+    }
+
+    #[test]
+    fn test_result_independent_of_threads() {
+        let code = parse("2,5,6,0,99,0,0");
+        let mut seek = GoalSeek::new(vec![(5, 0..=12), (6, 0..=12)]);
+        let results = (1..=5)
+            .map(|threads| {
+                seek.threads = threads;
+                seek.search(&code, |outcome| outcome.memory.get(0) == 24)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(results.iter().all(|result| *result == results[0]));
+        assert_eq!(results[0].len(), 6);
+    }
+
+    #[test]
+    fn test_piecewise_not_affine() {
+        // Sets [0] to [16], plus 1000 if [16] is 50
+        let code = parse("1008,16,50,17,1006,17,11,1001,16,1000,16,1001,16,0,0,99,0,0");
+        let seek = GoalSeek::new(vec![(16, 0..=99)]);
+        assert_eq!(seek.affine(&code, Target::Memory(0)), None);
+        assert_eq!(
+            seek.solve(&code, Target::Memory(0), 1050).unwrap(),
+            vec![vec![50]]
+        );
+        assert_eq!(
+            seek.solve(&code, Target::Memory(0), 49).unwrap(),
+            vec![vec![49]]
+        );
+    }
+
+    #[test]
+    fn test_folding_does_not_overflow() {
+        // Sets [0] to ([22] + i64::MAX) + 1 if [22] is negative, otherwise to [22]
+        let code = parse(concat!(
+            "1007,22,0,21,1006,21,16,1001,22,9223372036854775807,0,",
+            "1001,0,1,0,99,1001,22,0,0,99,0,0"
+        ));
+        let seek = GoalSeek::new(vec![(22, -10..=10)]);
+        assert_eq!(seek.affine(&code, Target::Memory(0)), None);
+        assert_eq!(
+            seek.solve(&code, Target::Memory(0), i64::MAX - 4).unwrap(),
+            vec![vec![-5]]
+        );
+    }
+
+    #[test]
+    fn test_too_many_assignments() {
+        let seek = GoalSeek::new(vec![(5, 0..=1 << 40), (6, 0..=1 << 40)]);
+        assert_eq!(
+            seek.search(&parse("2,5,6,0,99,0,0"), |_| true),
+            Err(SearchError::TooManyAssignments)
+        );
+        // Solving for one wide cell is fine, as its values are never enumerated
+        let seek = GoalSeek::new(vec![(5, i64::MIN..=i64::MAX)]);
+        assert_eq!(
+            seek.solve(&parse("1,5,5,0,99,0"), Target::Memory(0), 2),
+            Ok(vec![vec![1]])
+        );
+        let seek = GoalSeek::new(vec![(5, i64::MIN..=i64::MAX), (6, i64::MIN..=i64::MAX)]);
+        assert_eq!(
+            seek.solve(&parse("1,5,6,0,99,0,0"), Target::Memory(0), 2),
+            Err(SearchError::TooManyAssignments)
+        );
+    }
+}
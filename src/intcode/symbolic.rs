@@ -0,0 +1,669 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use maplit::btreemap;
+
+use super::{IntcodeError, Opcode, ParamMode, Processor};
+
+/// An unknown value that a symbolic expression can depend on
+#[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Var {
+    /// The value given to the nth input instruction executed, counting from 0
+    Input(usize),
+    /// The initial value of the memory cell at this address
+    Cell(usize),
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Var::Input(n) => write!(f, "in{}", n),
+            Var::Cell(addr) => write!(f, "[{}]", addr),
+        }
+    }
+}
+
+/// A value computed by a program from its unknowns. Constants are folded as the expression is
+/// built, so an expression with no unknowns in it is always a `Const`.
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Expr {
+    Const(i64),
+    Var(Var),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    /// 1 if the left side is less than the right, otherwise 0
+    Lt(Box<Expr>, Box<Expr>),
+    /// 1 if both sides are equal, otherwise 0
+    Eq(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// The value of the expression when each unknown takes its value from `values`, or `None` if
+    /// one of them is missing or the arithmetic overflows
+    pub fn eval(&self, values: &BTreeMap<Var, i64>) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Var(var) => values.get(var).cloned(),
+            Expr::Add(a, b) => a.eval(values)?.checked_add(b.eval(values)?),
+            Expr::Mul(a, b) => a.eval(values)?.checked_mul(b.eval(values)?),
+            Expr::Lt(a, b) => Some((a.eval(values)? < b.eval(values)?) as i64),
+            Expr::Eq(a, b) => Some((a.eval(values)? == b.eval(values)?) as i64),
+        }
+    }
+
+    /// The expression as a constant plus a multiple of each unknown, or `None` if it is not
+    /// linear in its unknowns
+    pub fn linear(&self) -> Option<(i128, BTreeMap<Var, i128>)> {
+        match self {
+            Expr::Const(value) => Some((*value as i128, BTreeMap::new())),
+            Expr::Var(var) => Some((0, btreemap! { *var => 1 })),
+            Expr::Add(a, b) => {
+                let (mut constant, mut terms) = a.linear()?;
+                let (other_constant, other_terms) = b.linear()?;
+                constant = constant.checked_add(other_constant)?;
+                for (var, coefficient) in other_terms {
+                    let term = terms.entry(var).or_insert(0);
+                    *term = term.checked_add(coefficient)?;
+                }
+                terms.retain(|_, coefficient| *coefficient != 0);
+                Some((constant, terms))
+            }
+            Expr::Mul(a, b) => {
+                let (a, b) = (a.linear()?, b.linear()?);
+                let ((scale, _), (constant, terms)) = match (a.1.is_empty(), b.1.is_empty()) {
+                    (true, _) => (a, b),
+                    (_, true) => (b, a),
+                    _ => return None,
+                };
+                let terms = terms
+                    .into_iter()
+                    .map(|(var, coefficient)| Some((var, coefficient.checked_mul(scale)?)))
+                    .collect::<Option<BTreeMap<_, _>>>()?;
+                Some((constant.checked_mul(scale)?, terms))
+            }
+            Expr::Lt(_, _) | Expr::Eq(_, _) => None,
+        }
+    }
+
+    fn add(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.checked_add(b)?),
+            (Expr::Const(0), e) | (e, Expr::Const(0)) => e,
+            (Expr::Const(c), e) => return Expr::add(e, Expr::Const(c)),
+            (Expr::Add(e, c), Expr::Const(b)) => match *c {
+                // Only fold the constants together if that does not overflow, as running the
+                // program may not
+                Expr::Const(a) if a.checked_add(b).is_some() => {
+                    return Expr::add(*e, Expr::Const(a.checked_add(b)?))
+                }
+                c => Expr::Add(
+                    Box::new(Expr::Add(e, Box::new(c))),
+                    Box::new(Expr::Const(b)),
+                ),
+            },
+            (a, b) => Expr::Add(Box::new(a), Box::new(b)),
+        })
+    }
+
+    fn mul(a: Expr, b: Expr) -> Option<Expr> {
+        Some(match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.checked_mul(b)?),
+            (Expr::Const(0), _) | (_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), e) | (e, Expr::Const(1)) => e,
+            (Expr::Const(c), e) => return Expr::mul(e, Expr::Const(c)),
+            (Expr::Mul(e, c), Expr::Const(b)) => match *c {
+                Expr::Const(a) if a.checked_mul(b).is_some() => {
+                    return Expr::mul(*e, Expr::Const(a.checked_mul(b)?))
+                }
+                c => Expr::Mul(
+                    Box::new(Expr::Mul(e, Box::new(c))),
+                    Box::new(Expr::Const(b)),
+                ),
+            },
+            (a, b) => Expr::Mul(Box::new(a), Box::new(b)),
+        })
+    }
+
+    fn lt(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as i64),
+            (ref a, ref b) if a == b => Expr::Const(0),
+            (a, b) => Expr::Lt(Box::new(a), Box::new(b)),
+        }
+    }
+
+    fn eq(a: Expr, b: Expr) -> Expr {
+        match (a, b) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as i64),
+            (ref a, ref b) if a == b => Expr::Const(1),
+            (a, b) => Expr::Eq(Box::new(a), Box::new(b)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Var(var) => write!(f, "{}", var),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "({} * {})", a, b),
+            Expr::Lt(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Eq(a, b) => write!(f, "({} == {})", a, b),
+        }
+    }
+}
+
+/// A branch taken on a symbolic value: `expr` was nonzero, or was zero
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Condition {
+    pub expr: Expr,
+    pub nonzero: bool,
+}
+
+impl Condition {
+    /// Whether the condition holds for `values`, or `None` if that cannot be decided from them
+    pub fn holds(&self, values: &BTreeMap<Var, i64>) -> Option<bool> {
+        self.expr
+            .eval(values)
+            .map(|value| (value != 0) == self.nonzero)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} 0",
+            self.expr,
+            if self.nonzero { "!=" } else { "==" }
+        )
+    }
+}
+
+/// Why exploration of a path stopped
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PathEnd {
+    Halted,
+    /// The path would fail with this error whatever the values of its unknowns
+    Error(IntcodeError),
+    /// The instruction at `pc` needs a symbolic value as an instruction, address, jump target or
+    /// relative base offset, which this executor cannot follow
+    Unresolved {
+        pc: usize,
+    },
+    /// The path ran for `SymbolicExecutor::max_steps` instructions without stopping
+    StepLimit,
+}
+
+impl fmt::Display for PathEnd {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PathEnd::Halted => write!(f, "halted"),
+            PathEnd::Error(error) => write!(f, "failed: {}", error),
+            PathEnd::Unresolved { pc } => write!(f, "needs a symbolic address at {}", pc),
+            PathEnd::StepLimit => write!(f, "step limit reached"),
+        }
+    }
+}
+
+/// One route through a program, as found by `SymbolicExecutor::explore`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Path {
+    /// The branches taken on symbolic values along the way, in order
+    pub conditions: Vec<Condition>,
+    /// Everything output along the way, in order
+    pub outputs: Vec<Expr>,
+    /// The final value of every cell that was written or marked symbolic
+    pub memory: BTreeMap<usize, Expr>,
+    /// How many input instructions were executed
+    pub inputs: usize,
+    pub end: PathEnd,
+}
+
+impl Path {
+    /// Values for the unknowns that make the `index`th output equal `value` while following this
+    /// path. Only outputs that are linear in a single unknown can be solved, and all of the path's
+    /// conditions must be decidable from that unknown alone; otherwise this gives `None`, as it
+    /// does when the value cannot be reached on this path.
+    pub fn solve_output(&self, index: usize, value: i64) -> Option<BTreeMap<Var, i64>> {
+        let (constant, terms) = self.outputs.get(index)?.linear()?;
+        let values = match terms.into_iter().collect::<Vec<_>>().as_slice() {
+            [] if constant == value as i128 => BTreeMap::new(),
+            [(var, coefficient)] => {
+                let remainder = value as i128 - constant;
+                if remainder % coefficient != 0 {
+                    return None;
+                }
+                let x = remainder / coefficient;
+                if x < i64::MIN as i128 || x > i64::MAX as i128 {
+                    return None;
+                }
+                btreemap! { *var => x as i64 }
+            }
+            _ => return None,
+        };
+
+        for condition in &self.conditions {
+            if !condition.holds(&values)? {
+                return None;
+            }
+        }
+        Some(values)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.conditions.is_empty() {
+            writeln!(f, "always:")?;
+        } else {
+            let conditions = self
+                .conditions
+                .iter()
+                .map(|condition| condition.to_string())
+                .collect::<Vec<_>>();
+            writeln!(f, "if {}:", conditions.join(", "))?;
+        }
+        for output in &self.outputs {
+            writeln!(f, "    output {}", output)?;
+        }
+        write!(f, "    {}", self.end)
+    }
+}
+
+/// Runs a program with unknown input, and optionally unknown memory cells, following both sides
+/// of every branch that depends on them.
+///
+/// Loops whose exit depends on an unknown are unrolled once per iteration, each exit giving a
+/// separate path, so exploration is bounded by `max_steps` per path and `max_paths` in total.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SymbolicExecutor {
+    /// Addresses whose initial values are treated as unknowns rather than read from the image
+    pub symbolic_cells: Vec<usize>,
+    /// How many instructions a single path may execute before it is abandoned
+    pub max_steps: usize,
+    /// How many paths to find before stopping
+    pub max_paths: usize,
+}
+
+impl Default for SymbolicExecutor {
+    fn default() -> SymbolicExecutor {
+        SymbolicExecutor::new()
+    }
+}
+
+impl SymbolicExecutor {
+    pub fn new() -> SymbolicExecutor {
+        SymbolicExecutor {
+            symbolic_cells: Vec::new(),
+            max_steps: 10_000,
+            max_paths: 1_000,
+        }
+    }
+
+    /// Every path through `code`, in the order exploration finished them. At a branch the
+    /// fall-through side is explored first.
+    pub fn explore(&self, code: &[i64]) -> Vec<Path> {
+        let start = State {
+            memory: self
+                .symbolic_cells
+                .iter()
+                .map(|&addr| (addr, Expr::Var(Var::Cell(addr))))
+                .collect(),
+            pc: 0,
+            rel: 0,
+            conditions: Vec::new(),
+            outputs: Vec::new(),
+            inputs: 0,
+            steps: 0,
+        };
+
+        let mut paths = Vec::new();
+        let mut pending = vec![start];
+        while paths.len() < self.max_paths {
+            let mut state = match pending.pop() {
+                Some(state) => state,
+                None => break,
+            };
+            let end = loop {
+                if state.steps == self.max_steps {
+                    break PathEnd::StepLimit;
+                }
+                state.steps += 1;
+                match state.step(code) {
+                    Ok(Step::Continue) => {}
+                    Ok(Step::Fork(taken)) => pending.push(*taken),
+                    Ok(Step::End(end)) => break end,
+                    Err(error) => break PathEnd::Error(error),
+                }
+            };
+            paths.push(state.finish(end));
+        }
+        paths
+    }
+}
+
+/// The outcome of executing one instruction symbolically
+enum Step {
+    Continue,
+    /// The instruction branched on a symbolic value. The current state follows the fall-through
+    /// side, and this one the jump.
+    Fork(Box<State>),
+    End(PathEnd),
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    memory: HashMap<usize, Expr>,
+    pc: usize,
+    rel: i64,
+    conditions: Vec<Condition>,
+    outputs: Vec<Expr>,
+    inputs: usize,
+    steps: usize,
+}
+
+impl State {
+    fn step(&mut self, code: &[i64]) -> Result<Step, IntcodeError> {
+        let raw_opcode = match self.load(code, self.pc) {
+            Expr::Const(raw_opcode) => raw_opcode,
+            _ => return Ok(self.unresolved()),
+        };
+        let (opcode, modes) = Processor::parse_opcode(self.pc, raw_opcode)?;
+        let opcode = Opcode::from_code(opcode).ok_or(IntcodeError::UnknownOpcode {
+            pc: self.pc,
+            opcode: raw_opcode,
+        })?;
+        let modes = [modes.0, modes.1, modes.2];
+
+        let mut addrs = Vec::new();
+        for (param, mode) in modes.iter().enumerate().take(opcode.num_params()) {
+            match self.param_addr(code, param + 1, *mode)? {
+                Some(addr) => addrs.push(addr),
+                None => return Ok(self.unresolved()),
+            }
+        }
+        let arg = |state: &State, param: usize| state.load(code, addrs[param]);
+        let overflow = IntcodeError::Overflow { pc: self.pc };
+
+        match opcode {
+            Opcode::Halt => return Ok(Step::End(PathEnd::Halted)),
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+                let (a, b) = (arg(self, 0), arg(self, 1));
+                let result = match opcode {
+                    Opcode::Add => Expr::add(a, b).ok_or(overflow)?,
+                    Opcode::Mul => Expr::mul(a, b).ok_or(overflow)?,
+                    Opcode::Lt => Expr::lt(a, b),
+                    _ => Expr::eq(a, b),
+                };
+                self.memory.insert(addrs[2], result);
+            }
+            Opcode::Read => {
+                self.memory
+                    .insert(addrs[0], Expr::Var(Var::Input(self.inputs)));
+                self.inputs += 1;
+            }
+            Opcode::Output => {
+                let value = arg(self, 0);
+                self.outputs.push(value);
+            }
+            Opcode::Jnz | Opcode::Jz => {
+                let target = match arg(self, 1) {
+                    Expr::Const(target) if target < 0 => {
+                        return Err(IntcodeError::NegativeAddress {
+                            pc: self.pc,
+                            addr: target,
+                        })
+                    }
+                    Expr::Const(target) => target as usize,
+                    _ => return Ok(self.unresolved()),
+                };
+                let jump_if_nonzero = opcode == Opcode::Jnz;
+                match arg(self, 0) {
+                    Expr::Const(cond) => {
+                        if (cond != 0) == jump_if_nonzero {
+                            self.pc = target;
+                            return Ok(Step::Continue);
+                        }
+                    }
+                    cond => {
+                        let mut taken = self.clone();
+                        taken.pc = target;
+                        let taken = taken.assumed(cond.clone(), jump_if_nonzero);
+                        let falls_through = self.assume(cond, !jump_if_nonzero);
+                        match (taken, falls_through) {
+                            (Some(taken), true) => {
+                                self.pc += 3;
+                                return Ok(Step::Fork(Box::new(taken)));
+                            }
+                            (Some(taken), false) => {
+                                *self = taken;
+                                return Ok(Step::Continue);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Opcode::Rel => match arg(self, 0) {
+                Expr::Const(offset) => {
+                    self.rel = self.rel.checked_add(offset).ok_or(overflow)?;
+                }
+                _ => return Ok(self.unresolved()),
+            },
+        }
+
+        self.pc += 1 + opcode.num_params();
+        Ok(Step::Continue)
+    }
+
+    /// Record that `cond` was nonzero, or zero, returning false if the path has already assumed
+    /// the opposite. A contradiction is only spotted when the same expression was assumed before.
+    fn assume(&mut self, cond: Expr, nonzero: bool) -> bool {
+        let mut repeated = false;
+        for condition in &self.conditions {
+            if condition.expr == cond {
+                if condition.nonzero != nonzero {
+                    return false;
+                }
+                repeated = true;
+            }
+        }
+        if !repeated {
+            self.conditions.push(Condition {
+                expr: cond,
+                nonzero,
+            });
+        }
+        true
+    }
+
+    /// A copy of this state having assumed `cond`, or `None` if that contradicts the path
+    fn assumed(mut self, cond: Expr, nonzero: bool) -> Option<State> {
+        if self.assume(cond, nonzero) {
+            Some(self)
+        } else {
+            None
+        }
+    }
+
+    /// The address of the `offset`th parameter, or `None` if it depends on an unknown
+    fn param_addr(
+        &self,
+        code: &[i64],
+        offset: usize,
+        mode: ParamMode,
+    ) -> Result<Option<usize>, IntcodeError> {
+        let addr = match (mode, self.load(code, self.pc + offset)) {
+            (ParamMode::Immediate, _) => return Ok(Some(self.pc + offset)),
+            (ParamMode::Position, Expr::Const(param)) => param,
+            (ParamMode::Relative, Expr::Const(param)) => self
+                .rel
+                .checked_add(param)
+                .ok_or(IntcodeError::Overflow { pc: self.pc })?,
+            _ => return Ok(None),
+        };
+        if addr < 0 {
+            Err(IntcodeError::NegativeAddress { pc: self.pc, addr })
+        } else {
+            Ok(Some(addr as usize))
+        }
+    }
+
+    fn load(&self, code: &[i64], addr: usize) -> Expr {
+        match self.memory.get(&addr) {
+            Some(expr) => expr.clone(),
+            None => Expr::Const(code.get(addr).cloned().unwrap_or(0)),
+        }
+    }
+
+    fn unresolved(&self) -> Step {
+        Step::End(PathEnd::Unresolved { pc: self.pc })
+    }
+
+    fn finish(self, end: PathEnd) -> Path {
+        Path {
+            conditions: self.conditions,
+            outputs: self.outputs,
+            memory: self.memory.into_iter().collect(),
+            inputs: self.inputs,
+            end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<i64> {
+        crate::intcode::parse_program(text).unwrap()
+    }
+
+    fn input(n: usize) -> Expr {
+        Expr::Var(Var::Input(n))
+    }
+
+    #[test]
+    fn test_straight_line() {
+        let paths = SymbolicExecutor::new().explore(&parse("3,9,1001,9,5,10,4,10,99,0,0"));
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].conditions, vec![]);
+        assert_eq!(paths[0].outputs[0].to_string(), "(in0 + 5)");
+        assert_eq!(paths[0].inputs, 1);
+        assert_eq!(paths[0].end, PathEnd::Halted);
+        assert_eq!(
+            paths[0].solve_output(0, 42),
+            Some(btreemap! { Var::Input(0) => 37 })
+        );
+    }
+
+    #[test]
+    fn test_branch() {
+        // Reads x, then outputs x * 2 if x < 10 and x otherwise
+        let code = parse("3,20,1007,20,10,21,1005,21,13,4,20,99,0,1002,20,2,22,4,22,99,0,0,0");
+        let paths = SymbolicExecutor::new().explore(&code);
+        assert_eq!(paths.len(), 2);
+        assert_eq!(
+            paths[0].to_string(),
+            "if (in0 < 10) == 0:\n    output in0\n    halted"
+        );
+        assert_eq!(
+            paths[1].to_string(),
+            "if (in0 < 10) != 0:\n    output (in0 * 2)\n    halted"
+        );
+
+        let solutions = paths
+            .iter()
+            .filter_map(|path| path.solve_output(0, 14))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            solutions,
+            vec![
+                btreemap! { Var::Input(0) => 14 },
+                btreemap! { Var::Input(0) => 7 }
+            ]
+        );
+        // 8 would take the other branch, and 15 is odd
+        assert_eq!(paths[0].solve_output(0, 8), None);
+        assert_eq!(paths[1].solve_output(0, 15), None);
+    }
+
+    #[test]
+    fn test_loop_unrolling() {
+        // Reads n, then outputs n, n - 1, ..., 1
+        let code = parse("3,12,4,12,1001,12,-1,12,1005,12,2,99,0");
+        let mut executor = SymbolicExecutor::new();
+        executor.max_paths = 3;
+        let paths = executor.explore(&code);
+        assert_eq!(
+            paths
+                .iter()
+                .map(|path| path.outputs.len())
+                .collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            paths[2].conditions,
+            vec![
+                Condition {
+                    expr: Expr::add(input(0), Expr::Const(-1)).unwrap(),
+                    nonzero: true
+                },
+                Condition {
+                    expr: Expr::add(input(0), Expr::Const(-2)).unwrap(),
+                    nonzero: true
+                },
+                Condition {
+                    expr: Expr::add(input(0), Expr::Const(-3)).unwrap(),
+                    nonzero: false
+                },
+            ]
+        );
+        assert_eq!(
+            paths[2].solve_output(2, 1),
+            Some(btreemap! { Var::Input(0) => 3 })
+        );
+
+        executor.max_paths = 100;
+        executor.max_steps = 20;
+        let paths = executor.explore(&code);
+        assert_eq!(paths.last().unwrap().end, PathEnd::StepLimit);
+    }
+
+    #[test]
+    fn test_contradictions_pruned() {
+        // Halts if x is 0, otherwise checks again and outputs x if it is now 0
+        let code = parse("3,13,1005,13,6,99,1006,13,10,99,4,13,99,0");
+        let paths = SymbolicExecutor::new().explore(&code);
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|path| path.outputs.is_empty()));
+    }
+
+    #[test]
+    fn test_symbolic_cells() {
+        let mut executor = SymbolicExecutor::new();
+        executor.symbolic_cells = vec![1, 2];
+        let paths = executor.explore(&parse("1101,0,0,0,99"));
+        assert_eq!(paths[0].memory[&0].to_string(), "([1] + [2])");
+
+        // Used as addresses, as in day 2, they cannot be followed
+        let paths = executor.explore(&parse("1,0,0,0,99"));
+        assert_eq!(paths[0].end, PathEnd::Unresolved { pc: 0 });
+    }
+
+    #[test]
+    fn test_linear() {
+        let expr = Expr::add(
+            Expr::mul(Expr::add(input(0), Expr::Const(3)).unwrap(), Expr::Const(4)).unwrap(),
+            Expr::Var(Var::Cell(7)),
+        )
+        .unwrap();
+        assert_eq!(
+            expr.linear(),
+            Some((12, btreemap! { Var::Input(0) => 4, Var::Cell(7) => 1 }))
+        );
+        assert_eq!(Expr::mul(input(0), input(1)).unwrap().linear(), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::fmt::Display;
+
+use super::Opcode;
+
+/// Receives a notification for everything a `Processor` does while executing.
+///
+/// Every method has an empty default implementation, so an observer only needs to implement the
+/// events it cares about. The processor is generic over its observer, so when none is attached
+/// (`NoObserver`) the calls compile away entirely. Values are passed by reference so that
+/// observing a processor with big integer cells does not clone every value it touches.
+pub trait Observer<C = i64> {
+    /// An instruction at `pc` has been decoded and is about to execute
+    fn instruction(&mut self, _pc: usize, _opcode: Opcode) {}
+
+    /// An instruction read `value` from the parameter at `addr`. An immediate parameter is
+    /// reported at its own address, as the instruction reads it from there. Decoding the
+    /// instruction and reading the addresses of its other parameters are not reported.
+    fn memory_read(&mut self, _addr: usize, _value: &C) {}
+
+    /// An instruction replaced the value `old` at `addr` with `new`
+    fn memory_write(&mut self, _addr: usize, _old: &C, _new: &C) {}
+
+    /// An input instruction consumed `value`
+    fn input(&mut self, _value: &C) {}
+
+    /// An output instruction produced `value`
+    fn output(&mut self, _value: &C) {}
+}
+
+impl<C, O: Observer<C> + ?Sized> Observer<C> for &mut O {
+    fn instruction(&mut self, pc: usize, opcode: Opcode) {
+        (**self).instruction(pc, opcode)
+    }
+
+    fn memory_read(&mut self, addr: usize, value: &C) {
+        (**self).memory_read(addr, value)
+    }
+
+    fn memory_write(&mut self, addr: usize, old: &C, new: &C) {
+        (**self).memory_write(addr, old, new)
+    }
+
+    fn input(&mut self, value: &C) {
+        (**self).input(value)
+    }
+
+    fn output(&mut self, value: &C) {
+        (**self).output(value)
+    }
+}
+
+/// The observer used when nobody is watching
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NoObserver;
+
+impl<C> Observer<C> for NoObserver {}
+
+/// Prints a line to stdout for every event, for quick debugging
+#[derive(Debug, Copy, Clone, Default)]
+pub struct TracePrinter;
+
+impl<C: Display> Observer<C> for TracePrinter {
+    fn instruction(&mut self, pc: usize, opcode: Opcode) {
+        println!("PC {}: {}", pc, opcode.mnemonic());
+    }
+
+    fn memory_read(&mut self, addr: usize, value: &C) {
+        println!("    read [{}] = {}", addr, value);
+    }
+
+    fn memory_write(&mut self, addr: usize, old: &C, new: &C) {
+        println!("    write [{}] = {} (was {})", addr, new, old);
+    }
+
+    fn input(&mut self, value: &C) {
+        println!("    input {}", value);
+    }
+
+    fn output(&mut self, value: &C) {
+        println!("    output {}", value);
+    }
+}
+
+/// A single event reported to an `Observer`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TraceEvent<C = i64> {
+    Instruction { pc: usize, opcode: Opcode },
+    MemoryRead { addr: usize, value: C },
+    MemoryWrite { addr: usize, old: C, new: C },
+    Input(C),
+    Output(C),
+}
+
+/// Records every event in order, for later inspection
+#[derive(Debug, Clone)]
+pub struct TraceRecorder<C = i64> {
+    pub events: Vec<TraceEvent<C>>,
+}
+
+impl<C> TraceRecorder<C> {
+    pub fn new() -> TraceRecorder<C> {
+        TraceRecorder { events: Vec::new() }
+    }
+}
+
+impl<C> Default for TraceRecorder<C> {
+    fn default() -> TraceRecorder<C> {
+        TraceRecorder::new()
+    }
+}
+
+impl<C: Clone> Observer<C> for TraceRecorder<C> {
+    fn instruction(&mut self, pc: usize, opcode: Opcode) {
+        self.events.push(TraceEvent::Instruction { pc, opcode });
+    }
+
+    fn memory_read(&mut self, addr: usize, value: &C) {
+        self.events.push(TraceEvent::MemoryRead {
+            addr,
+            value: value.clone(),
+        });
+    }
+
+    fn memory_write(&mut self, addr: usize, old: &C, new: &C) {
+        self.events.push(TraceEvent::MemoryWrite {
+            addr,
+            old: old.clone(),
+            new: new.clone(),
+        });
+    }
+
+    fn input(&mut self, value: &C) {
+        self.events.push(TraceEvent::Input(value.clone()));
+    }
+
+    fn output(&mut self, value: &C) {
+        self.events.push(TraceEvent::Output(value.clone()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::{Processor, ProcessorState};
+
+    #[test]
+    fn test_record_events() {
+        let mut recorder = TraceRecorder::new();
+        let result = Processor::new(vec![3, 9, 1001, 9, 5, 10, 4, 10, 99, 0, 0])
+            .execute_observed(deque!(37), &mut recorder)
+            .unwrap();
+        assert_eq!(result, (ProcessorState::Halted, deque!(42)));
+        assert_eq!(
+            recorder.events,
+            vec![
+                TraceEvent::Instruction {
+                    pc: 0,
+                    opcode: Opcode::Read
+                },
+                TraceEvent::Input(37),
+                TraceEvent::MemoryWrite {
+                    addr: 9,
+                    old: 0,
+                    new: 37
+                },
+                TraceEvent::Instruction {
+                    pc: 2,
+                    opcode: Opcode::Add
+                },
+                TraceEvent::MemoryRead { addr: 9, value: 37 },
+                TraceEvent::MemoryRead { addr: 4, value: 5 },
+                TraceEvent::MemoryWrite {
+                    addr: 10,
+                    old: 0,
+                    new: 42
+                },
+                TraceEvent::Instruction {
+                    pc: 6,
+                    opcode: Opcode::Output
+                },
+                TraceEvent::MemoryRead {
+                    addr: 10,
+                    value: 42
+                },
+                TraceEvent::Output(42),
+                TraceEvent::Instruction {
+                    pc: 8,
+                    opcode: Opcode::Halt
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn test_blocked_input_not_reported() {
+        let mut recorder = TraceRecorder::new();
+        let mut proc = Processor::new(vec![3, 3, 99, 0]);
+        assert_eq!(
+            proc.execute_observed(deque!(), &mut recorder).unwrap(),
+            (ProcessorState::IoWait, deque!())
+        );
+        assert_eq!(recorder.events, vec![]);
+    }
+
+    #[test]
+    fn test_custom_observer() {
+        #[derive(Default)]
+        struct Counter {
+            instructions: usize,
+            writes: usize,
+        }
+
+        impl Observer for Counter {
+            fn instruction(&mut self, _pc: usize, _opcode: Opcode) {
+                self.instructions += 1;
+            }
+
+            fn memory_write(&mut self, _addr: usize, _old: &i64, _new: &i64) {
+                self.writes += 1;
+            }
+        }
+
+        let mut counter = Counter::default();
+        Processor::new(vec![1101, 1, 2, 7, 1102, 3, 4, 8, 99])
+            .execute_observed(deque!(), &mut counter)
+            .unwrap();
+        assert_eq!((counter.instructions, counter.writes), (3, 2));
+    }
+}
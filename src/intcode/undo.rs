@@ -0,0 +1,46 @@
+use super::Observer;
+
+/// Everything needed to reverse one executed instruction, as recorded by
+/// `Processor::step_undoable` and applied by `Processor::undo`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Undo<C = i64> {
+    /// The address of the instruction, which is where execution resumes once it is undone
+    pub pc: usize,
+    /// The relative base before the instruction ran
+    pub rel: i64,
+    /// The extent of memory before the instruction ran
+    pub extent: usize,
+    /// The previous value of each cell the instruction wrote, in the order written
+    pub writes: Vec<(usize, C)>,
+    /// The value the instruction read as input, which the caller should give back to its input
+    pub input: Option<C>,
+    /// The value the instruction output, which the caller should take back from its output
+    pub output: Option<C>,
+}
+
+impl<C> Undo<C> {
+    pub(crate) fn new(pc: usize, rel: i64, extent: usize) -> Undo<C> {
+        Undo {
+            pc,
+            rel,
+            extent,
+            writes: Vec::new(),
+            input: None,
+            output: None,
+        }
+    }
+}
+
+impl<C: Clone> Observer<C> for Undo<C> {
+    fn memory_write(&mut self, addr: usize, old: &C, _new: &C) {
+        self.writes.push((addr, old.clone()));
+    }
+
+    fn input(&mut self, value: &C) {
+        self.input = Some(value.clone());
+    }
+
+    fn output(&mut self, value: &C) {
+        self.output = Some(value.clone());
+    }
+}
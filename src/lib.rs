@@ -0,0 +1,19 @@
+#[macro_export(local_inner_macros)]
+macro_rules! deque {
+    (@single $($x:tt)*) => (());
+    (@count $($rest:expr),*) => (<[()]>::len(&[$(deque!(@single $rest)),*]));
+
+    ($($key:expr,)+) => { deque!($($key),+) };
+    ($($key:expr),*) => {
+        {
+            let _cap = deque!(@count $($key),*);
+            let mut _set = ::std::collections::VecDeque::with_capacity(_cap);
+            $(
+                let _ = _set.push_back($key);
+            )*
+            _set
+        }
+    };
+}
+
+pub mod intcode;